@@ -0,0 +1,63 @@
+//! Checkers implemented in Rust.
+
+use std::{collections::BTreeMap, fmt, sync::Arc};
+
+/// A checker that runs in the judging process.
+///
+/// Register one with [`Checkers::register`] and refer to it from a test suite with
+/// `match: { Custom: { name: <name> } }`.
+pub trait Checker: Send + Sync {
+    /// Checks `actual`.
+    ///
+    /// `expected` is the `out` of the test case, if any.
+    fn check(&self, input: &str, expected: Option<&str>, actual: &str) -> CheckResult;
+}
+
+impl<F: Fn(&str, Option<&str>, &str) -> CheckResult + Send + Sync> Checker for F {
+    fn check(&self, input: &str, expected: Option<&str>, actual: &str) -> CheckResult {
+        self(input, expected, actual)
+    }
+}
+
+/// A result of [`Checker::check`].
+///
+/// The messages are shown as the stderr of the checker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckResult {
+    Accepted,
+    WrongAnswer {
+        message: String,
+    },
+    PresentationError {
+        message: String,
+    },
+    /// The checker itself failed.
+    Failed {
+        reason: String,
+    },
+}
+
+/// Named [`Checker`]s.
+#[derive(Clone, Default)]
+pub struct Checkers(BTreeMap<String, Arc<dyn Checker>>);
+
+impl Checkers {
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        checker: impl Checker + 'static,
+    ) -> &mut Self {
+        self.0.insert(name.into(), Arc::new(checker));
+        self
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&Arc<dyn Checker>> {
+        self.0.get(name)
+    }
+}
+
+impl fmt::Debug for Checkers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
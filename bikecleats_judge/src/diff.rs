@@ -0,0 +1,376 @@
+//! Diffs between expected and actual outputs.
+
+use crate::{parse_to_tokens, Token};
+use bikecleats_testsuite::DeterministicExpectedOutput;
+use similar::{Algorithm, DiffOp, DiffTag};
+use std::{cmp, io, ops::Range};
+use termcolor::{Color, WriteColor};
+use unicode_width::UnicodeWidthStr as _;
+
+/// How [`JudgeOutcome::print_pretty`] shows the outputs of Wrong Answer verdicts.
+///
+/// [`JudgeOutcome::print_pretty`]: crate::JudgeOutcome::print_pretty
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffStyle {
+    /// Prints the expected and actual outputs one after the other.
+    None,
+    /// Prints a unified diff.
+    Unified,
+    /// Prints the expected and actual outputs side by side if they fit in `terminal_width`
+    /// columns. Otherwise falls back to `Unified`.
+    SideBySide { terminal_width: usize },
+}
+
+/// The number of unchanged lines shown around changes.
+const CONTEXT_LINES: usize = 3;
+
+pub(crate) fn write_diff(
+    mut wtr: impl WriteColor,
+    expected: &str,
+    actual: &str,
+    style: DiffStyle,
+    highlight_numbers: bool,
+) -> io::Result<()> {
+    let expected = Lines::new(expected, highlight_numbers);
+    let actual = Lines::new(actual, highlight_numbers);
+
+    let ops = similar::capture_diff_slices(Algorithm::Myers, &expected.raw, &actual.raw);
+    let groups = similar::group_diff_ops(ops, CONTEXT_LINES);
+
+    if let DiffStyle::SideBySide { terminal_width } = style {
+        let column_width = cmp::max(expected.max_width(), actual.max_width());
+        let column_width = cmp::max(column_width, HEADER_WIDTH);
+        if column_width * 2 + SEPARATOR_WIDTH <= terminal_width {
+            return write_side_by_side(wtr, &expected, &actual, &groups, column_width);
+        }
+    }
+
+    wtr.set_color(color_spec!(Bold, Fg(Color::Red)))?;
+    writeln!(wtr, "--- expected")?;
+    wtr.set_color(color_spec!(Bold, Fg(Color::Green)))?;
+    writeln!(wtr, "+++ actual")?;
+    wtr.reset()?;
+
+    for group in &groups {
+        let (old, new) = (
+            hunk_range(group, DiffOp::old_range),
+            hunk_range(group, DiffOp::new_range),
+        );
+        wtr.set_color(color_spec!(Fg(Color::Cyan)))?;
+        writeln!(
+            wtr,
+            "@@ -{},{} +{},{} @@",
+            old.start + 1,
+            old.len(),
+            new.start + 1,
+            new.len(),
+        )?;
+        wtr.reset()?;
+
+        for op in group {
+            let (tag, old, new) = op.as_tag_tuple();
+            match tag {
+                DiffTag::Equal => {
+                    for i in old {
+                        write!(wtr, " ")?;
+                        write_line(&mut wtr, &expected.tokens[i], None, &[])?;
+                        writeln!(wtr)?;
+                    }
+                }
+                DiffTag::Delete | DiffTag::Insert | DiffTag::Replace => {
+                    let emphases = old
+                        .clone()
+                        .zip(new.clone())
+                        .map(|(i, j)| emphases(&expected.tokens[i], &actual.tokens[j]))
+                        .collect::<Vec<_>>();
+
+                    for (k, i) in old.enumerate() {
+                        let emphasized = emphases.get(k).map_or(&[][..], |(e, _)| e);
+                        wtr.set_color(color_spec!(Fg(Color::Red)))?;
+                        write!(wtr, "-")?;
+                        wtr.reset()?;
+                        write_line(&mut wtr, &expected.tokens[i], Some(Color::Red), emphasized)?;
+                        writeln!(wtr)?;
+                    }
+                    for (k, j) in new.enumerate() {
+                        let emphasized = emphases.get(k).map_or(&[][..], |(_, e)| e);
+                        wtr.set_color(color_spec!(Fg(Color::Green)))?;
+                        write!(wtr, "+")?;
+                        wtr.reset()?;
+                        write_line(&mut wtr, &actual.tokens[j], Some(Color::Green), emphasized)?;
+                        writeln!(wtr)?;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the candidate whose expected output has the most lines in common with `actual`.
+pub(crate) fn closest<'a>(
+    candidates: &'a [DeterministicExpectedOutput],
+    actual: &str,
+) -> Option<&'a DeterministicExpectedOutput> {
+    let actual = actual.split_inclusive('\n').collect::<Vec<_>>();
+    candidates
+        .iter()
+        .map(|candidate| {
+            let expected = candidate
+                .expected_stdout()
+                .unwrap_or("")
+                .split_inclusive('\n')
+                .collect::<Vec<_>>();
+            let ops = similar::capture_diff_slices(Algorithm::Myers, &expected, &actual);
+            let ratio = similar::get_diff_ratio(&ops, expected.len(), actual.len());
+            (candidate, ratio)
+        })
+        .rev()
+        .max_by(|(_, r1), (_, r2)| r1.total_cmp(r2))
+        .map(|(candidate, _)| candidate)
+}
+
+const SEPARATOR_WIDTH: usize = 3;
+
+/// The width of `expected`, the header of the left column.
+const HEADER_WIDTH: usize = 8;
+
+fn write_side_by_side(
+    mut wtr: impl WriteColor,
+    expected: &Lines<'_>,
+    actual: &Lines<'_>,
+    groups: &[Vec<DiffOp>],
+    column_width: usize,
+) -> io::Result<()> {
+    wtr.set_color(color_spec!(Bold, Fg(Color::Red)))?;
+    write!(wtr, "{:<width$}", "expected", width = column_width)?;
+    wtr.reset()?;
+    write!(wtr, "   ")?;
+    wtr.set_color(color_spec!(Bold, Fg(Color::Green)))?;
+    writeln!(wtr, "actual")?;
+    wtr.reset()?;
+
+    for (n, group) in groups.iter().enumerate() {
+        if n > 0 {
+            wtr.set_color(color_spec!(Fg(Color::Cyan)))?;
+            writeln!(
+                wtr,
+                "{:^width$}",
+                "...",
+                width = column_width * 2 + SEPARATOR_WIDTH
+            )?;
+            wtr.reset()?;
+        }
+
+        for op in group {
+            let (tag, old, new) = op.as_tag_tuple();
+            let rows = cmp::max(old.len(), new.len());
+            for k in 0..rows {
+                let left = old.clone().nth(k).map(|i| &expected.tokens[i]);
+                let right = new.clone().nth(k).map(|j| &actual.tokens[j]);
+
+                let (left_emphases, right_emphases) = match (tag, left, right) {
+                    (DiffTag::Replace, Some(left), Some(right)) => emphases(left, right),
+                    _ => (vec![], vec![]),
+                };
+                let (left_color, right_color, separator) = match (tag, left, right) {
+                    (DiffTag::Equal, ..) => (None, None, ' '),
+                    (_, Some(_), Some(_)) => (Some(Color::Red), Some(Color::Green), '|'),
+                    (_, Some(_), None) => (Some(Color::Red), None, '<'),
+                    (_, None, _) => (None, Some(Color::Green), '>'),
+                };
+
+                let left_width = if let Some(left) = left {
+                    write_line(&mut wtr, left, left_color, &left_emphases)?
+                } else {
+                    0
+                };
+                write!(wtr, "{}", " ".repeat(column_width - left_width))?;
+
+                wtr.set_color(color_spec!(Fg(Color::Cyan)))?;
+                write!(wtr, " {} ", separator)?;
+                wtr.reset()?;
+
+                if let Some(right) = right {
+                    write_line(&mut wtr, right, right_color, &right_emphases)?;
+                }
+                writeln!(wtr)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+struct Lines<'a> {
+    /// Lines including the `\n`, compared with each other.
+    raw: Vec<&'a str>,
+    tokens: Vec<Line<'a>>,
+}
+
+impl<'a> Lines<'a> {
+    fn new(text: &'a str, highlight_numbers: bool) -> Self {
+        let raw = text.split_inclusive('\n').collect::<Vec<_>>();
+        let tokens = raw
+            .iter()
+            .map(|line| {
+                let (content, missing_newline) = match line.strip_suffix('\n') {
+                    Some(content) => (content, false),
+                    None => (*line, true),
+                };
+                Line {
+                    tokens: parse_to_tokens(content, highlight_numbers),
+                    missing_newline,
+                }
+            })
+            .collect();
+        Self { raw, tokens }
+    }
+
+    fn max_width(&self) -> usize {
+        self.tokens.iter().map(Line::width).max().unwrap_or(0)
+    }
+}
+
+struct Line<'a> {
+    tokens: Vec<Token<'a>>,
+    missing_newline: bool,
+}
+
+impl Line<'_> {
+    fn width(&self) -> usize {
+        self.tokens.iter().map(Token::width).sum::<usize>() + usize::from(self.missing_newline)
+    }
+}
+
+/// Returns which tokens of `old` and `new` are changed.
+fn emphases(old: &Line<'_>, new: &Line<'_>) -> (Vec<bool>, Vec<bool>) {
+    let mut old_emphases = vec![false; old.tokens.len()];
+    let mut new_emphases = vec![false; new.tokens.len()];
+    for op in similar::capture_diff_slices(Algorithm::Myers, &old.tokens, &new.tokens) {
+        if op.tag() != DiffTag::Equal {
+            old_emphases[op.old_range()]
+                .iter_mut()
+                .for_each(|e| *e = true);
+            new_emphases[op.new_range()]
+                .iter_mut()
+                .for_each(|e| *e = true);
+        }
+    }
+    (old_emphases, new_emphases)
+}
+
+/// Writes `line` without the trailing newline, returning the width.
+fn write_line(
+    mut wtr: impl WriteColor,
+    line: &Line<'_>,
+    fg: Option<Color>,
+    emphases: &[bool],
+) -> io::Result<usize> {
+    for (i, token) in line.tokens.iter().enumerate() {
+        let emphasized = emphases.get(i).copied().unwrap_or(false);
+        crate::write_token(&mut wtr, token, fg, emphasized)?;
+    }
+    if line.missing_newline {
+        wtr.set_color(color_spec!(Fg(Color::Yellow)))?;
+        write!(wtr, "⏎")?;
+        wtr.reset()?;
+    }
+    Ok(line.width())
+}
+
+fn hunk_range(group: &[DiffOp], range: fn(&DiffOp) -> Range<usize>) -> Range<usize> {
+    let start = group.first().map_or(0, |op| range(op).start);
+    let end = group.last().map_or(0, |op| range(op).end);
+    start..end
+}
+
+impl Token<'_> {
+    /// Returns the width of this token printed by `write_token`.
+    fn width(&self) -> usize {
+        match *self {
+            Token::SpcLf(s) | Token::HighlightedNumber(s) | Token::Plain(s) => s.width(),
+            Token::Cr(n) | Token::Tab(n) => 2 * n,
+            Token::OtherWhitespaceControl(s) => s.escape_unicode().to_string().width(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{closest, emphases, write_diff, DiffStyle, Lines};
+    use bikecleats_testsuite::DeterministicExpectedOutput;
+    use termcolor::NoColor;
+
+    fn diff(expected: &str, actual: &str, style: DiffStyle) -> String {
+        let mut wtr = NoColor::new(vec![]);
+        write_diff(&mut wtr, expected, actual, style, false).unwrap();
+        String::from_utf8(wtr.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn unified() {
+        assert_eq!(
+            concat!(
+                "--- expected\n",
+                "+++ actual\n",
+                "@@ -1,3 +1,3 @@\n",
+                " 1\n",
+                "-2\n",
+                "+4\n",
+                " 3\n",
+            ),
+            diff("1\n2\n3\n", "1\n4\n3\n", DiffStyle::Unified),
+        );
+    }
+
+    #[test]
+    fn side_by_side() {
+        let style = DiffStyle::SideBySide { terminal_width: 80 };
+        assert_eq!(
+            concat!(
+                "expected   actual\n",
+                "1          1\n",
+                "22       | 4\n",
+                "333      < \n",
+            ),
+            diff("1\n22\n333\n", "1\n4\n", style),
+        );
+        // The left column is padded to the widest line, and a missing newline takes a column.
+        assert_eq!(
+            concat!(
+                "expected     actual\n",
+                "a            a\n",
+                "bbbbbbbbb⏎ | b\n",
+            ),
+            diff("a\nbbbbbbbbb", "a\nb\n", style),
+        );
+
+        // Falls back to the unified diff if the columns do not fit.
+        let style = DiffStyle::SideBySide { terminal_width: 18 };
+        assert!(diff("1\n", "2\n", style).starts_with("--- expected\n"));
+    }
+
+    #[test]
+    fn emphasize_changed_tokens() {
+        let (old, new) = (Lines::new("1 2 3\n", false), Lines::new("1 5 3\n", false));
+        let (old, new) = emphases(&old.tokens[0], &new.tokens[0]);
+        assert_eq!(vec![false, false, true, false, false], old);
+        assert_eq!(vec![false, false, true, false, false], new);
+    }
+
+    #[test]
+    fn closest_candidate() {
+        let candidates = ["1\n2\n3\n", "1\n5\n6\n", "1\n2\n4\n"]
+            .iter()
+            .map(|text| DeterministicExpectedOutput::Lines {
+                text: (*text).into(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(Some(&candidates[2]), closest(&candidates, "1\n2\n4\n5\n"),);
+        // Ties are broken by the order of the candidates.
+        assert_eq!(Some(&candidates[0]), closest(&candidates, "1\n"));
+        assert_eq!(None, closest(&[], "1\n"));
+    }
+}
@@ -0,0 +1,208 @@
+//! Filling in missing expected outputs.
+
+use crate::{open_file, run, CommandExpression, CtrlC};
+use anyhow::Context as _;
+use bikecleats_testsuite::{BatchTestSuite, DEFAULT_GENERATOR_TIMELIMIT};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+/// What [`fill_expected_outputs`] filled in.
+#[derive(Debug, Clone, Default)]
+pub struct FilledOutputs {
+    /// The indices of the test cases in `cases` whose `out` was set.
+    pub cases: Vec<usize>,
+    /// The output files written for `Text`.
+    pub files: Vec<PathBuf>,
+}
+
+/// Runs `reference` on the test cases that have no expected outputs, and writes the results back.
+///
+/// `out` of the test cases in `suite.cases` is set in place, so that `suite` can be saved with
+/// `TestSuite::to_yaml_pretty`. For `Text`, the output files are written next to the input files.
+///
+/// Each run of `reference` fails if it takes longer than `timelimit`, which defaults to
+/// [`DEFAULT_GENERATOR_TIMELIMIT`].
+pub fn fill_expected_outputs<C: 'static + Future<Output = tokio::io::Result<()>> + Send>(
+    draw_target: ProgressDrawTarget,
+    ctrl_c: fn() -> C,
+    reference: &CommandExpression,
+    suite: &mut BatchTestSuite,
+    parent_dir: &Path,
+    timelimit: Option<Duration>,
+) -> anyhow::Result<FilledOutputs> {
+    let timelimit = timelimit.unwrap_or(DEFAULT_GENERATOR_TIMELIMIT);
+    let text_cases = suite.text_cases_without_outputs(parent_dir)?;
+
+    let tempdir = tempfile::Builder::new()
+        .prefix("bikecleats-fill-")
+        .tempdir()?;
+    let input_path = tempdir.path().join("input");
+    let output_path = tempdir.path().join("output");
+
+    let pb = ProgressBar::new_spinner();
+    pb.set_draw_target(draw_target);
+    pb.set_style(ProgressStyle::default_spinner().template("{spinner} {msg:bold}"));
+    pb.enable_steady_tick(50);
+
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_io()
+        .enable_time()
+        .build()?;
+
+    let filled = rt.block_on(async {
+        let ctrl_c = CtrlC::spawn(ctrl_c);
+
+        let run_reference = |name: Option<String>, input: Arc<str>| {
+            let (ctrl_c, pb) = (ctrl_c.clone(), pb.clone());
+            let (input_path, output_path) = (&input_path, &output_path);
+            async move {
+                tokio::task::block_in_place(|| {
+                    pb.set_message(&format!(
+                        "Running the reference... ({:?})",
+                        name.as_deref().unwrap_or(""),
+                    ));
+                });
+                tokio::fs::write(input_path, input.as_ref()).await?;
+                run(
+                    reference,
+                    open_file(input_path).await?.into(),
+                    output_path,
+                    Some(timelimit),
+                    &mut ctrl_c.subscribe()?,
+                )
+                .await
+                .with_context(|| format!("The reference failed on {:?}", name.unwrap_or_default()))
+            }
+        };
+
+        let mut filled = FilledOutputs::default();
+
+        for (i, case) in suite.cases.iter_mut().enumerate() {
+            if case.out.is_none() {
+                case.out = Some(run_reference(case.name.clone(), case.r#in.clone()).await?);
+                filled.cases.push(i);
+            }
+        }
+
+        for (name, input, path) in text_cases {
+            let output = run_reference(Some(name), input).await?;
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::write(&path, output.as_ref())
+                .await
+                .with_context(|| format!("Could not write {}", path.display()))?;
+            filled.files.push(path);
+        }
+
+        Ok::<_, anyhow::Error>(filled)
+    });
+
+    pb.finish_and_clear();
+    tempdir.close()?;
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fill_expected_outputs;
+    use crate::CommandExpression;
+    use bikecleats_testsuite::BatchTestSuite;
+    use futures_util::future::{self, Pending};
+    use indicatif::ProgressDrawTarget;
+    use std::{env, fs, path::Path, time::Duration};
+
+    fn never() -> Pending<tokio::io::Result<()>> {
+        future::pending()
+    }
+
+    fn sh(script: &str) -> anyhow::Result<CommandExpression> {
+        Ok(CommandExpression {
+            program: "sh".into(),
+            args: vec!["-c".into(), script.into()],
+            cwd: env::current_dir()?,
+            env: Default::default(),
+        })
+    }
+
+    #[test]
+    fn fill() -> anyhow::Result<()> {
+        static SUITE: &str = r#"
+timelimit: 10s
+match: Lines
+cases:
+  - name: a
+    in: "1\n"
+  - name: b
+    in: "2\n"
+    out: "2\n"
+extend:
+  - type: Text
+    path: ./tests
+    in: /in/*.txt
+    out: /out/*.txt
+"#;
+
+        let tempdir = tempfile::Builder::new()
+            .prefix("bikecleats-fill-test-")
+            .tempdir()?;
+        let tests = tempdir.path().join("tests");
+        fs::create_dir_all(tests.join("in"))?;
+        fs::create_dir_all(tests.join("out"))?;
+        fs::write(tests.join("in").join("c.txt"), "3\n")?;
+        fs::write(tests.join("in").join("d.txt"), "4\n")?;
+        fs::write(tests.join("out").join("d.txt"), "4\n")?;
+
+        let mut suite = serde_yaml::from_str::<BatchTestSuite>(SUITE)?;
+        let filled = fill_expected_outputs(
+            ProgressDrawTarget::hidden(),
+            never,
+            &sh("sed s/^/x/")?,
+            &mut suite,
+            tempdir.path(),
+            None,
+        )?;
+
+        assert_eq!(vec![0], filled.cases);
+        assert_eq!(Some("x1\n"), suite.cases[0].out.as_deref());
+        assert_eq!(Some("2\n"), suite.cases[1].out.as_deref());
+        assert_eq!(vec![tests.join("out").join("c.txt")], filled.files);
+        assert_eq!("x3\n", fs::read_to_string(tests.join("out").join("c.txt"))?);
+        assert_eq!("4\n", fs::read_to_string(tests.join("out").join("d.txt"))?);
+        Ok(())
+    }
+
+    #[test]
+    fn timelimit() -> anyhow::Result<()> {
+        static SUITE: &str = r#"
+timelimit: 10s
+match: Lines
+cases:
+  - name: a
+    in: "1\n"
+"#;
+
+        let mut suite = serde_yaml::from_str::<BatchTestSuite>(SUITE)?;
+        let err = fill_expected_outputs(
+            ProgressDrawTarget::hidden(),
+            never,
+            &sh("sleep 100")?,
+            &mut suite,
+            Path::new(""),
+            Some(Duration::from_millis(100)),
+        )
+        .unwrap_err();
+        assert_eq!(r#"The reference failed on "a""#, err.to_string());
+        assert!(err
+            .root_cause()
+            .to_string()
+            .starts_with("timed out (100 ms)"));
+        assert_eq!(None, suite.cases[0].out);
+        Ok(())
+    }
+}
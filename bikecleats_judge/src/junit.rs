@@ -0,0 +1,289 @@
+//! JUnit XML reports.
+
+use crate::{JudgeOutcome, Verdict};
+use std::io;
+
+/// The maximum number of lines of expected/actual outputs in a `<failure>`.
+const EXCERPT_LINES: usize = 20;
+
+pub(crate) fn write(outcome: &JudgeOutcome, mut wtr: impl io::Write, name: &str) -> io::Result<()> {
+    let tests = outcome.verdicts.len();
+    let errors = outcome
+        .verdicts
+        .iter()
+        .filter(|v| {
+            matches!(
+                v,
+                Verdict::CheckerFailed { .. } | Verdict::InvalidInput { .. }
+            )
+        })
+        .count();
+    let failures = outcome
+        .verdicts
+        .iter()
+        .filter(|v| {
+            !matches!(
+                v,
+                Verdict::Accepted { .. }
+                    | Verdict::CheckerFailed { .. }
+                    | Verdict::InvalidInput { .. }
+            )
+        })
+        .count();
+    let time = outcome
+        .verdicts
+        .iter()
+        .map(|v| v.resource_usage().wall.as_secs_f64())
+        .sum::<f64>();
+
+    writeln!(wtr, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        wtr,
+        r#"<testsuites tests="{}" failures="{}" errors="{}" time="{:.3}">"#,
+        tests, failures, errors, time,
+    )?;
+    writeln!(
+        wtr,
+        r#"  <testsuite name="{}" tests="{}" failures="{}" errors="{}" time="{:.3}">"#,
+        escape(name),
+        tests,
+        failures,
+        errors,
+        time,
+    )?;
+
+    for (i, verdict) in outcome.verdicts.iter().enumerate() {
+        let test_case_name = verdict
+            .test_case_name()
+            .map(ToOwned::to_owned)
+            .unwrap_or_else(|| format!("#{}", i + 1));
+
+        write!(
+            wtr,
+            r#"    <testcase name="{}" classname="{}" time="{:.3}""#,
+            escape(&test_case_name),
+            escape(name),
+            verdict.resource_usage().wall.as_secs_f64(),
+        )?;
+
+        let element = match verdict {
+            Verdict::Accepted { .. } => None,
+            Verdict::CheckerFailed { .. } | Verdict::InvalidInput { .. } => Some("error"),
+            _ => Some("failure"),
+        };
+
+        let stdout = verdict.stdout().filter(|s| !s.is_empty());
+        let stderr = verdict.stderr().filter(|s| !s.is_empty());
+
+        if element.is_none() && stdout.is_none() && stderr.is_none() {
+            writeln!(wtr, "/>")?;
+            continue;
+        }
+        writeln!(wtr, ">")?;
+
+        if let Some(element) = element {
+            writeln!(
+                wtr,
+                r#"      <{} message="{}" type="{}">{}</{}>"#,
+                element,
+                escape(&verdict.summary()),
+                kind(verdict),
+                escape(&details(verdict)),
+                element,
+            )?;
+        }
+        if let Some(stdout) = stdout {
+            writeln!(wtr, "      <system-out>{}</system-out>", escape(stdout))?;
+        }
+        if let Some(stderr) = stderr {
+            writeln!(wtr, "      <system-err>{}</system-err>", escape(stderr))?;
+        }
+
+        writeln!(wtr, "    </testcase>")?;
+    }
+
+    writeln!(wtr, "  </testsuite>")?;
+    writeln!(wtr, "</testsuites>")?;
+    wtr.flush()
+}
+
+fn kind(verdict: &Verdict) -> &'static str {
+    match verdict {
+        Verdict::Accepted { .. } => "Accepted",
+        Verdict::WrongAnswer { .. } => "WrongAnswer",
+        Verdict::PresentationError { .. } => "PresentationError",
+        Verdict::RuntimeError { .. } => "RuntimeError",
+        Verdict::MemoryLimitExceeded { .. } => "MemoryLimitExceeded",
+        Verdict::TimelimitExceeded { .. } => "TimelimitExceeded",
+        Verdict::CheckerFailed { .. } => "CheckerFailed",
+        Verdict::InvalidInput { .. } => "InvalidInput",
+    }
+}
+
+fn details(verdict: &Verdict) -> String {
+    let mut details = verdict.summary() + "\n";
+
+    let expected = verdict
+        .expected_stdout()
+        .map(|s| ("expected", s))
+        .or_else(|| verdict.expected().example().map(|s| ("example", s)));
+    if let Some((header, expected)) = expected {
+        details += &format!("\n{}:\n{}", header, excerpt(expected));
+    }
+    if let Some(stdout) = verdict.stdout() {
+        details += &format!("\nactual:\n{}", excerpt(stdout));
+    }
+    if let Some(checker_stderr) = verdict.checker_stderr().filter(|s| !s.is_empty()) {
+        details += &format!("\nchecker stderr:\n{}", excerpt(checker_stderr));
+    }
+    for note in verdict.wrong_answer_notes() {
+        details += &format!("\nnote: {}", note);
+    }
+    details
+}
+
+fn excerpt(text: &str) -> String {
+    let num_lines = text.lines().count();
+    let mut excerpt = text
+        .lines()
+        .take(EXCERPT_LINES)
+        .map(|line| format!("{}\n", line))
+        .collect::<String>();
+    if num_lines > EXCERPT_LINES {
+        excerpt += &format!("... ({} more lines)\n", num_lines - EXCERPT_LINES);
+    }
+    excerpt
+}
+
+/// Escapes `s` for XML text and attribute values.
+///
+/// Characters that XML 1.0 does not allow are replaced with U+FFFD.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped += "&amp;",
+            '<' => escaped += "&lt;",
+            '>' => escaped += "&gt;",
+            '"' => escaped += "&quot;",
+            '\'' => escaped += "&apos;",
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() || c == '\u{fffe}' || c == '\u{ffff}' => escaped.push('\u{fffd}'),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, excerpt, write, EXCERPT_LINES};
+    use crate::{JudgeOutcome, ResourceUsage, Verdict};
+    use bikecleats_testsuite::{DeterministicExpectedOutput, ExpectedOutput};
+    use std::{sync::Arc, time::Duration};
+
+    #[test]
+    fn write_mixed_outcome() -> anyhow::Result<()> {
+        let expected = || {
+            ExpectedOutput::Deterministic(DeterministicExpectedOutput::Exact { text: "2\n".into() })
+        };
+        let resource_usage = |millis| ResourceUsage {
+            wall: Duration::from_millis(millis),
+            ..ResourceUsage::default()
+        };
+        let outcome = JudgeOutcome {
+            verdicts: vec![
+                Verdict::Accepted {
+                    test_case_name: Some("accepted".to_owned()),
+                    resource_usage: resource_usage(100),
+                    stdin: Arc::from("1\n"),
+                    stdout: Arc::from(""),
+                    stderr: Arc::from(""),
+                    checker_stdout: Arc::from(""),
+                    checker_stderr: Arc::from(""),
+                    expected: expected(),
+                    transcript: None,
+                },
+                Verdict::WrongAnswer {
+                    test_case_name: Some("wrong <answer>".to_owned()),
+                    resource_usage: resource_usage(200),
+                    stdin: Arc::from("1\n"),
+                    stdout: Arc::from("3\n"),
+                    stderr: Arc::from(""),
+                    checker_stdout: Arc::from(""),
+                    checker_stderr: Arc::from(""),
+                    expected: expected(),
+                    notes: vec![],
+                    transcript: None,
+                },
+                Verdict::CheckerFailed {
+                    test_case_name: None,
+                    resource_usage: resource_usage(300),
+                    stdin: Arc::from("1\n"),
+                    stdout: Arc::from("2\n"),
+                    stderr: Arc::from("debug\n"),
+                    checker_stdout: Arc::from(""),
+                    checker_stderr: Arc::from(""),
+                    expected: expected(),
+                    reason: "checker timed out (10000 ms)".to_owned(),
+                    transcript: None,
+                },
+            ],
+        };
+
+        let mut xml = vec![];
+        write(&outcome, &mut xml, "a & b")?;
+        assert_eq!(
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<testsuites tests=\"3\" failures=\"1\" errors=\"1\" time=\"0.600\">\n",
+                "  <testsuite name=\"a &amp; b\" tests=\"3\" failures=\"1\" errors=\"1\" time=\"0.600\">\n",
+                "    <testcase name=\"accepted\" classname=\"a &amp; b\" time=\"0.100\"/>\n",
+                "    <testcase name=\"wrong &lt;answer&gt;\" classname=\"a &amp; b\" time=\"0.200\">\n",
+                "      <failure message=\"Wrong Answer (200 ms)\" type=\"WrongAnswer\">",
+                "Wrong Answer (200 ms)\n\nexpected:\n2\n\nactual:\n3\n</failure>\n",
+                "      <system-out>3\n</system-out>\n",
+                "    </testcase>\n",
+                "    <testcase name=\"#3\" classname=\"a &amp; b\" time=\"0.300\">\n",
+                "      <error message=\"Checker Failed (300 ms, checker timed out (10000 ms))\" ",
+                "type=\"CheckerFailed\">Checker Failed (300 ms, checker timed out (10000 ms))\n",
+                "\nexpected:\n2\n\nactual:\n2\n</error>\n",
+                "      <system-out>2\n</system-out>\n",
+                "      <system-err>debug\n</system-err>\n",
+                "    </testcase>\n",
+                "  </testsuite>\n",
+                "</testsuites>\n",
+            ),
+            String::from_utf8(xml)?,
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn escape_markup_and_invalid_characters() {
+        assert_eq!(
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;",
+            escape(r#"<a href="x">Tom & Jerry's</a>"#),
+        );
+        assert_eq!("a\tb\r\nc\n", escape("a\tb\r\nc\n"));
+        assert_eq!(
+            "\u{fffd}\u{fffd}\u{fffd}\u{fffd}あ",
+            escape("\0\u{1b}\u{fffe}\u{ffff}あ"),
+        );
+    }
+
+    #[test]
+    fn excerpt_long_outputs() {
+        assert_eq!("1\n2\n", excerpt("1\n2"));
+
+        let text = (1..=EXCERPT_LINES + 3)
+            .map(|i| format!("{}\n", i))
+            .collect::<String>();
+        let excerpt = excerpt(&text);
+        assert_eq!(
+            Some(&*format!("{}", EXCERPT_LINES)),
+            excerpt.lines().nth(EXCERPT_LINES - 1),
+        );
+        assert!(excerpt.ends_with(&format!("{}\n... (3 more lines)\n", EXCERPT_LINES)));
+    }
+}
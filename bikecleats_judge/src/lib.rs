@@ -1,21 +1,34 @@
-use anyhow::{anyhow, bail};
-use bikecleats_testsuite::{BatchTestCase, CheckerShell, ExpectedOutput};
+use crate::process::Process;
+use anyhow::{anyhow, bail, ensure, Context as _};
+use bikecleats_testsuite::{
+    BatchTestCase, ByteSize, CheckerCommand, CheckerProtocol, CheckerShell,
+    DeterministicExpectedOutput, ExpectedOutput, InteractiveTestCase, TimelimitMode, Validator,
+    ValidatorProtocol,
+};
 use futures_util::{select, FutureExt as _};
 use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use itertools::Itertools as _;
+use serde::{Deserialize, Serialize};
 use std::{
+    borrow::Cow,
     cmp,
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     env,
-    ffi::{OsStr, OsString},
+    ffi::OsString,
+    fmt,
     future::Future,
-    io, iter,
+    io, iter, mem,
+    num::NonZeroUsize,
     path::{Path, PathBuf},
-    process::{ExitStatus, Output, Stdio},
-    sync::Arc,
+    process::{ExitStatus, Stdio},
+    sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
-use termcolor::{Color, WriteColor};
-use tokio::io::AsyncWriteExt as _;
+use termcolor::{Color, ColorSpec, WriteColor};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _},
+    sync::broadcast,
+};
 use unicode_width::UnicodeWidthStr as _;
 
 macro_rules! color_spec {
@@ -51,8 +64,26 @@ macro_rules! _color_spec_inner {
     };
 }
 
+mod checker;
+mod diff;
+mod fill;
+mod junit;
+mod minimize;
+mod note;
+mod process;
+mod stress;
+
+pub use crate::{
+    checker::{CheckResult, Checker, Checkers},
+    diff::DiffStyle,
+    fill::{fill_expected_outputs, FilledOutputs},
+    minimize::{minimize, MinimizeSettings, Minimized},
+    note::{FloatError, Position, WrongAnswerNote},
+    stress::{stress, Counterexample, StressSettings},
+};
+
 #[non_exhaustive]
-#[derive(Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct JudgeOutcome {
     pub verdicts: Vec<Verdict>,
 }
@@ -62,6 +93,7 @@ impl JudgeOutcome {
         &self,
         mut wtr: W,
         display_limit: Option<usize>,
+        diff_style: DiffStyle,
     ) -> io::Result<()> {
         for (i, verdict) in self.verdicts.iter().enumerate() {
             if i > 0 {
@@ -80,163 +112,154 @@ impl JudgeOutcome {
             writeln!(wtr, "{}", verdict.summary())?;
             wtr.reset()?;
 
-            let mut write_text =
-                |header: &str, text: &str, highlight_numbers: bool| -> io::Result<()> {
-                    wtr.set_color(color_spec!(Bold, Fg(Color::Magenta)))?;
-                    writeln!(wtr, "{}", header)?;
-                    wtr.reset()?;
-
-                    if text.is_empty() {
-                        wtr.set_color(color_spec!(Bold, Fg(Color::Yellow)))?;
-                        writeln!(wtr, "EMPTY")?;
-                        return wtr.reset();
-                    }
-
-                    if matches!(display_limit, Some(l) if l < text.len()) {
-                        wtr.set_color(color_spec!(Bold, Fg(Color::Yellow)))?;
-                        writeln!(wtr, "{} B", text.len())?;
-                        return wtr.reset();
-                    }
-
-                    for token in parse_to_tokens(text, highlight_numbers) {
-                        match token {
-                            Token::SpcLf(s) | Token::Plain(s) => wtr.write_all(s.as_ref())?,
-                            Token::Cr(n) => {
-                                wtr.set_color(color_spec!(Fg(Color::Yellow)))?;
-                                (0..n).try_for_each(|_| wtr.write_all(b"\\r"))?;
-                                wtr.reset()?;
-                            }
-                            Token::Tab(n) => {
-                                wtr.set_color(color_spec!(Fg(Color::Yellow)))?;
-                                (0..n).try_for_each(|_| wtr.write_all(b"\\t"))?;
-                                wtr.reset()?;
-                            }
-                            Token::OtherWhitespaceControl(s) => {
-                                wtr.set_color(color_spec!(Fg(Color::Yellow)))?;
-                                write!(wtr, "{}", s.escape_unicode())?;
-                                wtr.reset()?;
-                            }
-                            Token::HighlightedNumber(s) => {
-                                wtr.set_color(color_spec!(Fg(Color::Cyan)))?;
-                                wtr.write_all(s.as_ref())?;
-                                wtr.reset()?;
-                            }
-                        }
-                    }
+            let write_text = |wtr: &mut W,
+                              header: &str,
+                              text: &str,
+                              highlight_numbers: bool|
+             -> io::Result<()> {
+                wtr.set_color(color_spec!(Bold, Fg(Color::Magenta)))?;
+                writeln!(wtr, "{}", header)?;
+                wtr.reset()?;
+
+                if text.is_empty() {
+                    wtr.set_color(color_spec!(Bold, Fg(Color::Yellow)))?;
+                    writeln!(wtr, "EMPTY")?;
+                    return wtr.reset();
+                }
 
-                    if !text.ends_with('\n') {
-                        wtr.set_color(color_spec!(Fg(Color::Yellow)))?;
-                        writeln!(wtr, "⏎")?;
-                        wtr.reset()?;
-                    }
+                if matches!(display_limit, Some(l) if l < text.len()) {
+                    wtr.set_color(color_spec!(Bold, Fg(Color::Yellow)))?;
+                    writeln!(wtr, "{} B", text.len())?;
+                    return wtr.reset();
+                }
 
-                    Ok(())
-                };
+                write_tokens(wtr, text, highlight_numbers)
+            };
 
-            write_text("stdin:", verdict.stdin(), false)?;
-            if let Some(expected) = verdict.expected().expected_stdout() {
-                write_text("expected:", expected, verdict.expected().is_float())?;
-            } else if let Some(example) = verdict.expected().example() {
-                write_text("example:", example, verdict.expected().is_float())?;
-            }
-            if let Some(stdout) = verdict.stdout() {
-                write_text("actual:", stdout, verdict.expected().is_float())?;
+            let diff = verdict
+                .expected_stdout()
+                .zip(verdict.stdout())
+                .filter(|_| {
+                    matches!(verdict, Verdict::WrongAnswer { .. }) && diff_style != DiffStyle::None
+                })
+                .filter(|(e, a)| !matches!(display_limit, Some(l) if l < e.len() || l < a.len()));
+
+            write_text(&mut wtr, "stdin:", verdict.stdin(), false)?;
+            if let Some((expected, actual)) = diff {
+                wtr.set_color(color_spec!(Bold, Fg(Color::Magenta)))?;
+                writeln!(
+                    wtr,
+                    "{}",
+                    verdict.expected_header().replacen("expected", "diff", 1)
+                )?;
+                wtr.reset()?;
+                diff::write_diff(
+                    &mut wtr,
+                    expected,
+                    actual,
+                    diff_style,
+                    verdict.expected().is_float(),
+                )?;
+            } else {
+                if let Some(expected) = verdict.expected_stdout() {
+                    write_text(
+                        &mut wtr,
+                        &verdict.expected_header(),
+                        expected,
+                        verdict.expected().is_float(),
+                    )?;
+                } else if let Some(example) = verdict.expected().example() {
+                    write_text(&mut wtr, "example:", example, verdict.expected().is_float())?;
+                }
+                if let Some(stdout) = verdict.stdout().filter(|_| verdict.transcript().is_none()) {
+                    write_text(&mut wtr, "actual:", stdout, verdict.expected().is_float())?;
+                }
             }
             if let Some(stderr) = verdict.stderr().filter(|s| !s.is_empty()) {
-                write_text("stderr:", stderr, verdict.expected().is_float())?;
+                write_text(&mut wtr, "stderr:", stderr, verdict.expected().is_float())?;
             }
             if let Some(checker_stdout) = verdict.checker_stdout().filter(|s| !s.is_empty()) {
-                write_text("checker stdout: ", checker_stdout, false)?;
+                write_text(&mut wtr, "checker stdout: ", checker_stdout, false)?;
             }
             if let Some(checker_stderr) = verdict.checker_stderr().filter(|s| !s.is_empty()) {
-                write_text("checker stderr: ", checker_stderr, false)?;
+                write_text(&mut wtr, "checker stderr: ", checker_stderr, false)?;
             }
-            if let Some(wrong_answer_note) = verdict.wrong_answer_note() {
-                write_text("note: ", &(wrong_answer_note.to_string() + "\n"), false)?;
+            if let Some(validator_stdout) = verdict.validator_stdout().filter(|s| !s.is_empty()) {
+                write_text(&mut wtr, "validator stdout: ", validator_stdout, false)?;
+            }
+            if let Some(validator_stderr) = verdict.validator_stderr().filter(|s| !s.is_empty()) {
+                write_text(&mut wtr, "validator stderr: ", validator_stderr, false)?;
+            }
+            if !verdict.wrong_answer_notes().is_empty() {
+                let notes = verdict
+                    .wrong_answer_notes()
+                    .iter()
+                    .map(|note| format!("{}\n", note))
+                    .collect::<String>();
+                write_text(&mut wtr, "notes:", &notes, false)?;
+            }
+            if let Some(transcript) = verdict.transcript() {
+                wtr.set_color(color_spec!(Bold, Fg(Color::Magenta)))?;
+                writeln!(wtr, "transcript:")?;
+                wtr.reset()?;
+                write_transcript(&mut wtr, transcript)?;
             }
         }
 
-        return wtr.flush();
-
-        #[derive(Debug)]
-        enum Token<'a> {
-            SpcLf(&'a str),
-            Cr(usize),
-            Tab(usize),
-            OtherWhitespaceControl(&'a str),
-            HighlightedNumber(&'a str),
-            Plain(&'a str),
-        }
-
-        fn parse_to_tokens(text: &str, highlight_numbers: bool) -> Vec<Token<'_>> {
-            use nom::branch::alt;
-            use nom::bytes::complete::take_while1;
-            use nom::character::complete::char;
-            use nom::combinator::recognize;
-            use nom::multi::{many0, many1};
-            use nom::number::complete::recognize_float;
-            use nom::IResult;
-
-            let (_, tokens) = many0(alt((
-                spc_lf,
-                cr,
-                tab,
-                other_whitespace_control,
-                highlighted_number_or_plain(highlight_numbers),
-            )))(text)
-            .unwrap();
-
-            return tokens;
+        wtr.flush()
+    }
 
-            fn spc_lf(input: &str) -> IResult<&str, Token<'_>> {
-                let (rest, target) = take_while1(|c| [' ', '\n'].contains(&c))(input)?;
-                Ok((rest, Token::SpcLf(target)))
-            }
+    /// Prints the conversations recorded by [`judge_interactive`].
+    pub fn print_transcripts<W: WriteColor>(&self, mut wtr: W) -> io::Result<()> {
+        for (i, verdict) in self.verdicts.iter().enumerate() {
+            let transcript = match verdict.transcript() {
+                Some(transcript) => transcript,
+                None => continue,
+            };
 
-            fn cr(input: &str) -> IResult<&str, Token<'_>> {
-                let (rest, target) = recognize(many1(char('\r')))(input)?;
-                Ok((rest, Token::Cr(target.len())))
-            }
+            write!(
+                wtr,
+                "{}/{} ({:?}) ",
+                i + 1,
+                self.verdicts.len(),
+                verdict.test_case_name().unwrap_or(""),
+            )?;
 
-            fn tab(input: &str) -> IResult<&str, Token<'_>> {
-                let (rest, target) = recognize(many1(char('\t')))(input)?;
-                Ok((rest, Token::Tab(target.len())))
-            }
+            wtr.set_color(color_spec!(Bold, Fg(verdict.summary_color())))?;
+            writeln!(wtr, "{}", verdict.summary())?;
+            wtr.reset()?;
 
-            fn other_whitespace_control(input: &str) -> IResult<&str, Token<'_>> {
-                let (rest, target) =
-                    take_while1(|c: char| c.is_whitespace() || c.is_control())(input)?;
-                Ok((rest, Token::OtherWhitespaceControl(target)))
-            }
+            write_transcript(&mut wtr, transcript)?;
+        }
 
-            fn highlighted_number_or_plain(
-                highlight_numbers: bool,
-            ) -> fn(&str) -> IResult<&str, Token<'_>> {
-                return if highlight_numbers {
-                    |input| highlighted_number(input).or_else(|_| plain(input))
-                } else {
-                    plain
-                };
+        wtr.flush()
+    }
 
-                fn highlighted_number(input: &str) -> IResult<&str, Token<'_>> {
-                    let (rest, target) = recognize_float(input)?;
-                    Ok((rest, Token::HighlightedNumber(target)))
-                }
+    /// Writes `self` as pretty-printed JSON.
+    pub fn write_json<W: io::Write>(&self, mut wtr: W) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut wtr, self)?;
+        writeln!(wtr)?;
+        wtr.flush()
+    }
 
-                fn plain(input: &str) -> IResult<&str, Token<'_>> {
-                    let (rest, target) =
-                        take_while1(|c: char| !(c.is_whitespace() || c.is_control()))(input)?;
-                    Ok((rest, Token::Plain(target)))
-                }
-            }
-        }
+    /// Writes `self` as a JUnit XML report with one `<testsuite>` named `name`.
+    pub fn write_junit_xml<W: io::Write>(&self, wtr: W, name: &str) -> io::Result<()> {
+        junit::write(self, wtr, name)
     }
 
-    pub fn error_on_fail(&self) -> anyhow::Result<()> {
+    /// Returns an error if any of the test cases failed.
+    ///
+    /// If `allow_presentation_error` is `true`, Presentation Error verdicts are not counted as
+    /// failures.
+    pub fn error_on_fail(&self, allow_presentation_error: bool) -> anyhow::Result<()> {
         let fails = self
             .verdicts
             .iter()
-            .filter(|v| !matches!(v, Verdict::Accepted { .. }))
+            .filter(|v| match v {
+                Verdict::Accepted { .. } => false,
+                Verdict::PresentationError { .. } => !allow_presentation_error,
+                _ => true,
+            })
             .count();
 
         if fails > 0 {
@@ -252,41 +275,274 @@ impl JudgeOutcome {
     }
 }
 
-#[derive(Debug, Clone)]
+fn write_transcript(mut wtr: impl WriteColor, transcript: &Transcript) -> io::Result<()> {
+    if transcript.messages.is_empty() {
+        wtr.set_color(color_spec!(Bold, Fg(Color::Yellow)))?;
+        writeln!(wtr, "EMPTY")?;
+        return wtr.reset();
+    }
+
+    for message in &transcript.messages {
+        write!(wtr, "{:>6} ms ", message.elapsed.as_millis())?;
+        wtr.set_color(color_spec!(Bold, Fg(message.direction.color())))?;
+        write!(wtr, "{}", message.direction)?;
+        wtr.reset()?;
+        write!(wtr, " ")?;
+        write_tokens(&mut wtr, &message.text, false)?;
+    }
+    Ok(())
+}
+
+fn write_tokens(mut wtr: impl WriteColor, text: &str, highlight_numbers: bool) -> io::Result<()> {
+    for token in parse_to_tokens(text, highlight_numbers) {
+        write_token(&mut wtr, &token, None, false)?;
+    }
+
+    if !text.ends_with('\n') {
+        wtr.set_color(color_spec!(Fg(Color::Yellow)))?;
+        writeln!(wtr, "⏎")?;
+        wtr.reset()?;
+    }
+
+    Ok(())
+}
+
+/// Writes `token` in `fg`, underlining it if `emphasized`.
+fn write_token(
+    mut wtr: impl WriteColor,
+    token: &Token<'_>,
+    fg: Option<Color>,
+    emphasized: bool,
+) -> io::Result<()> {
+    let mut spec = ColorSpec::new();
+    spec.set_reset(false)
+        .set_underline(emphasized)
+        .set_bold(emphasized);
+
+    match *token {
+        Token::SpcLf(s) | Token::Plain(s) => {
+            wtr.set_color(spec.set_fg(fg))?;
+            wtr.write_all(s.as_ref())?;
+        }
+        Token::Cr(n) => {
+            wtr.set_color(spec.set_fg(Some(Color::Yellow)))?;
+            (0..n).try_for_each(|_| wtr.write_all(b"\\r"))?;
+        }
+        Token::Tab(n) => {
+            wtr.set_color(spec.set_fg(Some(Color::Yellow)))?;
+            (0..n).try_for_each(|_| wtr.write_all(b"\\t"))?;
+        }
+        Token::OtherWhitespaceControl(s) => {
+            wtr.set_color(spec.set_fg(Some(Color::Yellow)))?;
+            write!(wtr, "{}", s.escape_unicode())?;
+        }
+        Token::HighlightedNumber(s) => {
+            wtr.set_color(spec.set_fg(fg.or(Some(Color::Cyan))))?;
+            wtr.write_all(s.as_ref())?;
+        }
+    }
+    wtr.reset()
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Token<'a> {
+    SpcLf(&'a str),
+    Cr(usize),
+    Tab(usize),
+    OtherWhitespaceControl(&'a str),
+    HighlightedNumber(&'a str),
+    Plain(&'a str),
+}
+
+fn parse_to_tokens(text: &str, highlight_numbers: bool) -> Vec<Token<'_>> {
+    use nom::branch::alt;
+    use nom::bytes::complete::take_while1;
+    use nom::character::complete::char;
+    use nom::combinator::recognize;
+    use nom::multi::{many0, many1};
+    use nom::number::complete::recognize_float;
+    use nom::IResult;
+
+    let (_, tokens) = many0(alt((
+        spc_lf,
+        cr,
+        tab,
+        other_whitespace_control,
+        highlighted_number_or_plain(highlight_numbers),
+    )))(text)
+    .unwrap();
+
+    return tokens;
+
+    fn spc_lf(input: &str) -> IResult<&str, Token<'_>> {
+        let (rest, target) = take_while1(|c| [' ', '\n'].contains(&c))(input)?;
+        Ok((rest, Token::SpcLf(target)))
+    }
+
+    fn cr(input: &str) -> IResult<&str, Token<'_>> {
+        let (rest, target) = recognize(many1(char('\r')))(input)?;
+        Ok((rest, Token::Cr(target.len())))
+    }
+
+    fn tab(input: &str) -> IResult<&str, Token<'_>> {
+        let (rest, target) = recognize(many1(char('\t')))(input)?;
+        Ok((rest, Token::Tab(target.len())))
+    }
+
+    fn other_whitespace_control(input: &str) -> IResult<&str, Token<'_>> {
+        let (rest, target) = take_while1(|c: char| c.is_whitespace() || c.is_control())(input)?;
+        Ok((rest, Token::OtherWhitespaceControl(target)))
+    }
+
+    fn highlighted_number_or_plain(
+        highlight_numbers: bool,
+    ) -> fn(&str) -> IResult<&str, Token<'_>> {
+        return if highlight_numbers {
+            |input| highlighted_number(input).or_else(|_| plain(input))
+        } else {
+            plain
+        };
+
+        fn highlighted_number(input: &str) -> IResult<&str, Token<'_>> {
+            let (rest, target) = recognize_float(input)?;
+            Ok((rest, Token::HighlightedNumber(target)))
+        }
+
+        fn plain(input: &str) -> IResult<&str, Token<'_>> {
+            let (rest, target) =
+                take_while1(|c: char| !(c.is_whitespace() || c.is_control()))(input)?;
+            Ok((rest, Token::Plain(target)))
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "verdict")]
 pub enum Verdict {
     Accepted {
         test_case_name: Option<String>,
-        elapsed: Duration,
+        resource_usage: ResourceUsage,
+        #[serde(with = "serde_fn::arc_str")]
         stdin: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
         stdout: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
         stderr: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
+        checker_stdout: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
+        checker_stderr: Arc<str>,
         expected: ExpectedOutput,
+        transcript: Option<Transcript>,
     },
     WrongAnswer {
         test_case_name: Option<String>,
-        elapsed: Duration,
+        resource_usage: ResourceUsage,
+        #[serde(with = "serde_fn::arc_str")]
+        stdin: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
+        stdout: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
+        stderr: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
+        checker_stdout: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
+        checker_stderr: Arc<str>,
+        expected: ExpectedOutput,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        notes: Vec<WrongAnswerNote>,
+        transcript: Option<Transcript>,
+    },
+    PresentationError {
+        test_case_name: Option<String>,
+        resource_usage: ResourceUsage,
+        #[serde(with = "serde_fn::arc_str")]
         stdin: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
         stdout: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
         stderr: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
         checker_stdout: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
         checker_stderr: Arc<str>,
         expected: ExpectedOutput,
-        note: Option<WrongAnswerNote>,
+        transcript: Option<Transcript>,
     },
     RuntimeError {
         test_case_name: Option<String>,
-        elapsed: Duration,
+        resource_usage: ResourceUsage,
+        #[serde(with = "serde_fn::arc_str")]
         stdin: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
         stdout: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
         stderr: Arc<str>,
         expected: ExpectedOutput,
+        #[serde(with = "serde_fn::exit_status")]
         status: ExitStatus,
+        transcript: Option<Transcript>,
+    },
+    MemoryLimitExceeded {
+        test_case_name: Option<String>,
+        memory_limit: ByteSize,
+        resource_usage: ResourceUsage,
+        #[serde(with = "serde_fn::arc_str")]
+        stdin: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
+        stdout: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
+        stderr: Arc<str>,
+        expected: ExpectedOutput,
+        transcript: Option<Transcript>,
     },
     TimelimitExceeded {
         test_case_name: Option<String>,
+        #[serde(with = "serde_fn::duration_secs")]
         timelimit: Duration,
+        resource_usage: ResourceUsage,
+        #[serde(with = "serde_fn::arc_str")]
+        stdin: Arc<str>,
+        expected: ExpectedOutput,
+        transcript: Option<Transcript>,
+    },
+    /// The checker could not judge the output, or the validator could not judge the input. This
+    /// is not a fault of the solution.
+    ///
+    /// If the validator failed, the solution was not run, and `resource_usage`, `checker_stdout`,
+    /// and `checker_stderr` are those of the validator.
+    CheckerFailed {
+        test_case_name: Option<String>,
+        resource_usage: ResourceUsage,
+        #[serde(with = "serde_fn::arc_str")]
+        stdin: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
+        stdout: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
+        stderr: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
+        checker_stdout: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
+        checker_stderr: Arc<str>,
+        expected: ExpectedOutput,
+        reason: String,
+        transcript: Option<Transcript>,
+    },
+    /// The validator rejected the input, so the solution was not run. This is not a fault of the
+    /// solution.
+    InvalidInput {
+        test_case_name: Option<String>,
+        /// The resources used by the validator.
+        resource_usage: ResourceUsage,
+        #[serde(with = "serde_fn::arc_str")]
         stdin: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
+        validator_stdout: Arc<str>,
+        #[serde(with = "serde_fn::arc_str")]
+        validator_stderr: Arc<str>,
         expected: ExpectedOutput,
+        reason: String,
+        transcript: Option<Transcript>,
     },
 }
 
@@ -295,8 +551,12 @@ impl Verdict {
         match self {
             Verdict::Accepted { test_case_name, .. }
             | Verdict::WrongAnswer { test_case_name, .. }
+            | Verdict::PresentationError { test_case_name, .. }
             | Verdict::RuntimeError { test_case_name, .. }
-            | Verdict::TimelimitExceeded { test_case_name, .. } => test_case_name.as_deref(),
+            | Verdict::MemoryLimitExceeded { test_case_name, .. }
+            | Verdict::TimelimitExceeded { test_case_name, .. }
+            | Verdict::CheckerFailed { test_case_name, .. }
+            | Verdict::InvalidInput { test_case_name, .. } => test_case_name.as_deref(),
         }
     }
 
@@ -304,8 +564,12 @@ impl Verdict {
         match self {
             Verdict::Accepted { stdin, .. }
             | Verdict::WrongAnswer { stdin, .. }
+            | Verdict::PresentationError { stdin, .. }
             | Verdict::RuntimeError { stdin, .. }
-            | Verdict::TimelimitExceeded { stdin, .. } => stdin,
+            | Verdict::MemoryLimitExceeded { stdin, .. }
+            | Verdict::TimelimitExceeded { stdin, .. }
+            | Verdict::CheckerFailed { stdin, .. }
+            | Verdict::InvalidInput { stdin, .. } => stdin,
         }
     }
 
@@ -313,8 +577,11 @@ impl Verdict {
         match self {
             Verdict::Accepted { stdout, .. }
             | Verdict::WrongAnswer { stdout, .. }
-            | Verdict::RuntimeError { stdout, .. } => Some(stdout),
-            Verdict::TimelimitExceeded { .. } => None,
+            | Verdict::PresentationError { stdout, .. }
+            | Verdict::RuntimeError { stdout, .. }
+            | Verdict::MemoryLimitExceeded { stdout, .. }
+            | Verdict::CheckerFailed { stdout, .. } => Some(stdout),
+            Verdict::TimelimitExceeded { .. } | Verdict::InvalidInput { .. } => None,
         }
     }
 
@@ -322,8 +589,11 @@ impl Verdict {
         match self {
             Verdict::Accepted { stderr, .. }
             | Verdict::WrongAnswer { stderr, .. }
-            | Verdict::RuntimeError { stderr, .. } => Some(stderr),
-            Verdict::TimelimitExceeded { .. } => None,
+            | Verdict::PresentationError { stderr, .. }
+            | Verdict::RuntimeError { stderr, .. }
+            | Verdict::MemoryLimitExceeded { stderr, .. }
+            | Verdict::CheckerFailed { stderr, .. } => Some(stderr),
+            Verdict::TimelimitExceeded { .. } | Verdict::InvalidInput { .. } => None,
         }
     }
 
@@ -331,70 +601,294 @@ impl Verdict {
         match self {
             Verdict::Accepted { expected, .. }
             | Verdict::WrongAnswer { expected, .. }
+            | Verdict::PresentationError { expected, .. }
             | Verdict::RuntimeError { expected, .. }
-            | Verdict::TimelimitExceeded { expected, .. } => expected,
+            | Verdict::MemoryLimitExceeded { expected, .. }
+            | Verdict::TimelimitExceeded { expected, .. }
+            | Verdict::CheckerFailed { expected, .. }
+            | Verdict::InvalidInput { expected, .. } => expected,
+        }
+    }
+
+    /// Returns the expected output, or the candidate closest to the actual output for `AnyOf`.
+    fn expected_stdout(&self) -> Option<&str> {
+        match self.expected() {
+            ExpectedOutput::AnyOf(candidates) => {
+                diff::closest(candidates, self.stdout().unwrap_or(""))?.expected_stdout()
+            }
+            expected => expected.expected_stdout(),
+        }
+    }
+
+    fn expected_header(&self) -> String {
+        match self.expected() {
+            ExpectedOutput::AnyOf(candidates) => {
+                format!("expected (closest of {}):", candidates.len())
+            }
+            _ => "expected:".to_owned(),
         }
     }
 
     fn checker_stdout(&self) -> Option<&str> {
         match self {
-            Verdict::WrongAnswer { checker_stdout, .. } => Some(checker_stdout),
+            Verdict::Accepted { checker_stdout, .. }
+            | Verdict::WrongAnswer { checker_stdout, .. }
+            | Verdict::PresentationError { checker_stdout, .. }
+            | Verdict::CheckerFailed { checker_stdout, .. } => Some(checker_stdout),
             _ => None,
         }
     }
 
     fn checker_stderr(&self) -> Option<&str> {
         match self {
-            Verdict::WrongAnswer { checker_stderr, .. } => Some(checker_stderr),
+            Verdict::Accepted { checker_stderr, .. }
+            | Verdict::WrongAnswer { checker_stderr, .. }
+            | Verdict::PresentationError { checker_stderr, .. }
+            | Verdict::CheckerFailed { checker_stderr, .. } => Some(checker_stderr),
+            _ => None,
+        }
+    }
+
+    fn validator_stdout(&self) -> Option<&str> {
+        match self {
+            Verdict::InvalidInput {
+                validator_stdout, ..
+            } => Some(validator_stdout),
             _ => None,
         }
     }
 
-    fn wrong_answer_note(&self) -> Option<WrongAnswerNote> {
-        match *self {
-            Self::WrongAnswer { note, .. } => note,
+    fn validator_stderr(&self) -> Option<&str> {
+        match self {
+            Verdict::InvalidInput {
+                validator_stderr, ..
+            } => Some(validator_stderr),
             _ => None,
         }
     }
 
+    fn transcript(&self) -> Option<&Transcript> {
+        match self {
+            Verdict::Accepted { transcript, .. }
+            | Verdict::WrongAnswer { transcript, .. }
+            | Verdict::PresentationError { transcript, .. }
+            | Verdict::RuntimeError { transcript, .. }
+            | Verdict::MemoryLimitExceeded { transcript, .. }
+            | Verdict::TimelimitExceeded { transcript, .. }
+            | Verdict::CheckerFailed { transcript, .. }
+            | Verdict::InvalidInput { transcript, .. } => transcript.as_ref(),
+        }
+    }
+
+    fn wrong_answer_notes(&self) -> &[WrongAnswerNote] {
+        match self {
+            Self::WrongAnswer { notes, .. } => notes,
+            _ => &[],
+        }
+    }
+
+    fn resource_usage(&self) -> &ResourceUsage {
+        match self {
+            Verdict::Accepted { resource_usage, .. }
+            | Verdict::WrongAnswer { resource_usage, .. }
+            | Verdict::PresentationError { resource_usage, .. }
+            | Verdict::RuntimeError { resource_usage, .. }
+            | Verdict::MemoryLimitExceeded { resource_usage, .. }
+            | Verdict::TimelimitExceeded { resource_usage, .. }
+            | Verdict::CheckerFailed { resource_usage, .. }
+            | Verdict::InvalidInput { resource_usage, .. } => resource_usage,
+        }
+    }
+
     fn summary(&self) -> String {
+        let usage = self.resource_usage();
+
         match self {
-            Self::Accepted { elapsed, .. } => format!("Accepted ({} ms)", elapsed.as_millis()),
+            Self::Accepted { .. } => format!("Accepted ({})", usage),
             Self::TimelimitExceeded { timelimit, .. } => {
-                format!("Timelimit Exceeded ({} ms)", timelimit.as_millis())
+                format!(
+                    "Timelimit Exceeded ({} ms, {})",
+                    timelimit.as_millis(),
+                    usage
+                )
             }
-            Self::WrongAnswer { elapsed, .. } => {
-                format!("Wrong Answer ({} ms)", elapsed.as_millis())
+            Self::MemoryLimitExceeded { memory_limit, .. } => {
+                format!("Memory Limit Exceeded ({}, {})", memory_limit, usage)
             }
-            Self::RuntimeError {
-                elapsed, status, ..
-            } => format!("Runtime Error ({} ms, {})", elapsed.as_millis(), status),
+            Self::WrongAnswer { .. } => format!("Wrong Answer ({})", usage),
+            Self::PresentationError { .. } => format!("Presentation Error ({})", usage),
+            Self::RuntimeError { status, .. } => format!("Runtime Error ({}, {})", usage, status),
+            Self::CheckerFailed { reason, .. } => {
+                format!("Checker Failed ({}, {})", usage, reason)
+            }
+            Self::InvalidInput { reason, .. } => format!("Invalid Input ({})", reason),
         }
     }
 
     fn summary_color(&self) -> Color {
         match self {
             Self::Accepted { .. } => Color::Green,
-            Self::TimelimitExceeded { .. } => Color::Red,
+            Self::TimelimitExceeded { .. } | Self::MemoryLimitExceeded { .. } => Color::Red,
             Self::WrongAnswer { .. } | Self::RuntimeError { .. } => Color::Yellow,
+            Self::PresentationError { .. } => Color::Cyan,
+            Self::CheckerFailed { .. } | Self::InvalidInput { .. } => Color::Magenta,
         }
     }
 
     fn summary_style(&self) -> &'static str {
         match self {
             Self::Accepted { .. } => ".bold.green",
-            Self::TimelimitExceeded { .. } => ".bold.red",
+            Self::TimelimitExceeded { .. } | Self::MemoryLimitExceeded { .. } => ".bold.red",
             Self::WrongAnswer { .. } | Self::RuntimeError { .. } => ".bold.yellow",
+            Self::PresentationError { .. } => ".bold.cyan",
+            Self::CheckerFailed { .. } | Self::InvalidInput { .. } => ".bold.magenta",
+        }
+    }
+}
+
+/// Resources used by a solution.
+///
+/// The times are serialized in seconds.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default)]
+pub struct ResourceUsage {
+    #[serde(with = "serde_fn::duration_secs")]
+    pub wall: Duration,
+    /// The user CPU time. `None` if the platform does not report it.
+    #[serde(default, with = "serde_fn::option_duration_secs")]
+    pub user: Option<Duration>,
+    /// The system CPU time. `None` if the platform does not report it.
+    #[serde(default, with = "serde_fn::option_duration_secs")]
+    pub sys: Option<Duration>,
+    /// The peak resident set size. `None` if the platform does not report it.
+    pub max_rss: Option<ByteSize>,
+}
+
+impl ResourceUsage {
+    /// Returns the sum of the user and system CPU time.
+    pub fn cpu(&self) -> Option<Duration> {
+        Some(self.user? + self.sys?)
+    }
+
+    /// Returns the time compared with the time limit.
+    fn time(&self, mode: TimelimitMode) -> Duration {
+        match mode {
+            TimelimitMode::WallTime => self.wall,
+            TimelimitMode::CpuTime => self.cpu().unwrap_or(self.wall),
+        }
+    }
+
+    /// Returns whether a run that exited with `status` is judged as Memory Limit Exceeded.
+    ///
+    /// With `RLIMIT_DATA`, an allocation beyond the limit fails inside the solution, usually
+    /// before the peak RSS reaches the limit. A failing allocation tends to ask for about as much
+    /// as is already in use (e.g. when a buffer grows twice as large), so an abnormal exit after
+    /// using more than half of the limit is also attributed to the limit. A single allocation far
+    /// beyond the limit is still reported as Runtime Error.
+    fn exceeds_memory_limit(&self, memory_limit: Option<ByteSize>, status: ExitStatus) -> bool {
+        match (memory_limit, self.max_rss) {
+            (Some(limit), Some(max_rss)) => {
+                max_rss > limit || !status.success() && max_rss.bytes() > limit.bytes() / 2
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for ResourceUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ms", self.wall.as_millis())?;
+        if let (Some(user), Some(sys)) = (self.user, self.sys) {
+            write!(
+                f,
+                ", user {} ms, sys {} ms",
+                user.as_millis(),
+                sys.as_millis(),
+            )?;
+        }
+        if let Some(max_rss) = self.max_rss {
+            write!(
+                f,
+                ", {:.1} MiB",
+                max_rss.bytes() as f64 / f64::from(1 << 20)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A conversation between a solution and an interactor.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Transcript {
+    pub messages: Vec<TranscriptMessage>,
+}
+
+impl Transcript {
+    fn new(
+        from_solution: Vec<(Duration, Vec<u8>)>,
+        from_interactor: Vec<(Duration, Vec<u8>)>,
+    ) -> Self {
+        let from_solution = from_solution
+            .into_iter()
+            .map(|(elapsed, text)| (elapsed, Direction::SolutionToInteractor, text));
+
+        let from_interactor = from_interactor
+            .into_iter()
+            .map(|(elapsed, text)| (elapsed, Direction::InteractorToSolution, text));
+
+        let messages = from_solution
+            .merge_by(from_interactor, |(t1, ..), (t2, ..)| t1 <= t2)
+            .map(|(elapsed, direction, text)| TranscriptMessage {
+                elapsed,
+                direction,
+                text: String::from_utf8_lossy(&text).into(),
+            })
+            .collect();
+
+        Self { messages }
+    }
+}
+
+impl fmt::Display for Transcript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for TranscriptMessage {
+            elapsed,
+            direction,
+            text,
+        } in &self.messages
+        {
+            write!(f, "{:>6} ms {} {}", elapsed.as_millis(), direction, text)?;
+            if !text.ends_with('\n') {
+                writeln!(f)?;
+            }
         }
+        Ok(())
     }
 }
 
-#[derive(Copy, Clone, Debug, derive_more::Display)]
-pub enum WrongAnswerNote {
-    #[display(
-        fmt = "whitespace-separated words matched. try setting `match` to `SplitWhitespace`"
-    )]
-    WordsMatched,
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TranscriptMessage {
+    #[serde(with = "serde_fn::duration_secs")]
+    pub elapsed: Duration,
+    pub direction: Direction,
+    #[serde(with = "serde_fn::arc_str")]
+    pub text: Arc<str>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, derive_more::Display)]
+pub enum Direction {
+    #[display(fmt = "solution   >")]
+    SolutionToInteractor,
+    #[display(fmt = "interactor <")]
+    InteractorToSolution,
+}
+
+impl Direction {
+    fn color(self) -> Color {
+        match self {
+            Self::SolutionToInteractor => Color::Green,
+            Self::InteractorToSolution => Color::Blue,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -406,97 +900,282 @@ pub struct CommandExpression {
 }
 
 impl CommandExpression {
-    async fn build(
-        &self,
-        stdin: Option<&Path>,
-        stdout: &Path,
-        stderr: &Path,
-    ) -> io::Result<tokio::process::Command> {
-        let mut cmd = tokio::process::Command::new(&self.program);
-        let stdin = if let Some(stdin) = stdin {
-            tokio::fs::File::open(stdin).await?.into_std().await.into()
-        } else {
-            Stdio::piped()
-        };
-        let stdout = tokio::fs::File::create(stdout).await?.into_std().await;
-        let stderr = tokio::fs::File::create(stderr).await?.into_std().await;
+    fn build(&self, stdin: Stdio, stdout: Stdio, stderr: Stdio) -> std::process::Command {
+        let mut cmd = std::process::Command::new(&self.program);
         cmd.args(&self.args)
             .current_dir(&self.cwd)
             .envs(&self.env)
             .stdin(stdin)
             .stdout(stdout)
             .stderr(stderr);
-        Ok(cmd)
+        cmd
+    }
+}
+
+/// Settings for [`judge`] and [`judge_interactive`].
+#[derive(Debug, Clone, Default)]
+pub struct JudgeSettings {
+    /// The number of test cases judged at once. Defaults to the number of CPUs.
+    pub jobs: Option<NonZeroUsize>,
+    /// If `Some`, test cases are judged one at a time and `jobs` is ignored.
+    pub precise_timing: Option<PreciseTiming>,
+    /// Checkers referred to by `Custom` matches.
+    pub checkers: Checkers,
+}
+
+impl JudgeSettings {
+    fn jobs(&self) -> usize {
+        if self.precise_timing.is_some() {
+            1
+        } else {
+            self.jobs.map_or_else(num_cpus::get, NonZeroUsize::get)
+        }
+    }
+
+    fn warm_up(&self) -> bool {
+        matches!(
+            self.precise_timing,
+            Some(PreciseTiming { warm_up: true, .. })
+        )
+    }
+
+    fn cpu(&self) -> anyhow::Result<Option<usize>> {
+        let cpu = self.precise_timing.and_then(|p| p.cpu);
+        if let Some(cpu) = cpu {
+            ensure!(
+                process::is_cpu_available(cpu)?,
+                "CPU {} is not available to this process",
+                cpu,
+            );
+        }
+        Ok(cpu)
     }
 }
 
+/// Settings for judging test cases one at a time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PreciseTiming {
+    /// Runs the first test case once before judging and discards the result, so that the first
+    /// measurement does not include cold caches.
+    pub warm_up: bool,
+    /// Pins the solution to this CPU. Only supported on Linux, and the CPU must be in the affinity
+    /// mask of this process.
+    pub cpu: Option<usize>,
+}
+
+/// The default value of the time limit for checkers.
+pub const DEFAULT_CHECKER_TIMELIMIT: Duration = Duration::from_secs(10);
+
 pub fn judge<C: 'static + Future<Output = tokio::io::Result<()>> + Send>(
     draw_target: ProgressDrawTarget,
     ctrl_c: fn() -> C,
     cmd: &CommandExpression,
     test_cases: &[BatchTestCase],
+    settings: &JudgeSettings,
 ) -> anyhow::Result<JudgeOutcome> {
     let cmd = Arc::new(cmd.clone());
-    let num_test_cases = test_cases.len();
+    let cpu = settings.cpu()?;
+    let checkers = Arc::new(BatchCheckers::new(
+        test_cases,
+        &settings.checkers,
+        &cmd.cwd,
+    )?);
 
-    let quoted_name_width = test_cases
-        .iter()
-        .flat_map(|BatchTestCase { name, .. }| name.as_ref())
-        .map(|s| format!("{:?}", s).width())
-        .max()
-        .unwrap_or(0);
+    let tempdir = tempfile::Builder::new()
+        .prefix("snowchains-core-juding-")
+        .tempdir()?;
+    let tempdir_path = Arc::new(tempdir.path().to_owned());
+
+    let outcome = judge_in_parallel(
+        draw_target,
+        ctrl_c,
+        test_cases.to_owned(),
+        |BatchTestCase { name, .. }| name.as_deref(),
+        settings,
+        move |i, test_case, ctrl_c_rx| {
+            let (cmd, checkers, tempdir_path) =
+                (cmd.clone(), checkers.clone(), tempdir_path.clone());
+            async move {
+                judge_batch_test_case(i, test_case, &cmd, cpu, &checkers, &tempdir_path, ctrl_c_rx)
+                    .await
+            }
+        },
+    )?;
+
+    tempdir.close()?;
+    Ok(outcome)
+}
 
-    let bash_exe = {
-        static GIT_BASH: &str = r"C:\Program Files\Git\bin\bash.exe";
+/// Judges a solution for interactive problems.
+///
+/// The stdin and stdout of the solution are connected to the stdout and stdin of the interactor
+/// of each test case. If the solution exits successfully within the limits, the verdict is decided
+/// by the exit status of the interactor.
+pub fn judge_interactive<C: 'static + Future<Output = tokio::io::Result<()>> + Send>(
+    draw_target: ProgressDrawTarget,
+    ctrl_c: fn() -> C,
+    cmd: &CommandExpression,
+    test_cases: &[InteractiveTestCase],
+    settings: &JudgeSettings,
+) -> anyhow::Result<JudgeOutcome> {
+    let cmd = Arc::new(cmd.clone());
+    let cpu = settings.cpu()?;
 
-        let bash_exe = if cfg!(windows) && Path::new(GIT_BASH).exists() {
-            GIT_BASH
-        } else {
-            "bash"
-        };
-        which::which_in(bash_exe, env::var_os("PATH"), &cmd.cwd)
-            .map_err(|_| anyhow!("`{}` not found", bash_exe))?
-    };
+    let shells = test_cases
+        .iter()
+        .flat_map(|InteractiveTestCase { interactor, .. }| interactor.cmd.shell(interactor.shell));
+    let shells = Arc::new(find_shells(shells, &cmd.cwd)?);
 
     let tempdir = tempfile::Builder::new()
         .prefix("snowchains-core-juding-")
         .tempdir()?;
-    let tempdir_path = tempdir.path().to_owned();
-
-    let mp = MultiProgress::with_draw_target(draw_target);
+    let tempdir_path = Arc::new(tempdir.path().to_owned());
+
+    let outcome = judge_in_parallel(
+        draw_target,
+        ctrl_c,
+        test_cases.to_owned(),
+        |InteractiveTestCase { name, .. }| name.as_deref(),
+        settings,
+        move |i, test_case, ctrl_c_rx| {
+            let (cmd, shells, tempdir_path) = (cmd.clone(), shells.clone(), tempdir_path.clone());
+            async move {
+                judge_interactive_test_case(
+                    i,
+                    test_case,
+                    &cmd,
+                    cpu,
+                    &shells,
+                    &tempdir_path,
+                    ctrl_c_rx,
+                )
+                .await
+            }
+        },
+    )?;
 
-    let mut targets = vec![];
+    tempdir.close()?;
+    Ok(outcome)
+}
 
-    for (i, test_case) in test_cases.iter().enumerate() {
-        let pb = mp.add(ProgressBar::new_spinner());
+/// What the checkers of batch test cases run with.
+struct BatchCheckers {
+    shells: BTreeMap<CheckerShell, PathBuf>,
+    custom: Checkers,
+}
 
-        pb.set_style(progress_style("{prefix}{spinner} {msg:bold}"));
+impl BatchCheckers {
+    fn new(test_cases: &[BatchTestCase], custom: &Checkers, cwd: &Path) -> anyhow::Result<Self> {
+        let shells = test_cases.iter().flat_map(|test_case| {
+            let checker = match &test_case.output {
+                ExpectedOutput::Checker { cmd, shell, .. } => cmd.shell(*shell),
+                _ => None,
+            };
+            let validator = test_case
+                .validator
+                .as_ref()
+                .and_then(|Validator { cmd, shell, .. }| cmd.shell(*shell));
+            checker.into_iter().chain(validator)
+        });
+        let shells = find_shells(shells, cwd)?;
 
-        pb.set_prefix(&format!(
-            "{}/{} ({} ",
-            align_right(&(i + 1).to_string(), num_test_cases.to_string().len()),
-            num_test_cases,
-            align_left(
-                &format!("{:?})", test_case.name.as_deref().unwrap_or("")),
-                quoted_name_width + 1,
-            ),
-        ));
+        let validators = test_cases
+            .iter()
+            .flat_map(|test_case| &test_case.validator)
+            .dedup();
+        for Validator { cmd, shell, .. } in validators {
+            checker_command(&shells, cmd, *shell, cwd, &[])
+                .with_context(|| "Could not resolve the validator")?;
+        }
 
-        pb.set_message("Judging...");
-        pb.enable_steady_tick(50);
+        for test_case in test_cases {
+            if let ExpectedOutput::Custom { name, .. } = &test_case.output {
+                ensure!(custom.get(name).is_some(), "no such checker: {:?}", name);
+            }
+        }
 
-        targets.push((test_case.clone(), pb));
+        Ok(Self {
+            shells,
+            custom: custom.clone(),
+        })
     }
+}
 
-    let rt = tokio::runtime::Builder::new_multi_thread()
-        .enable_io()
-        .enable_time()
-        .build()?;
+/// Finds the executables of `shells`.
+fn find_shells(
+    shells: impl IntoIterator<Item = CheckerShell>,
+    cwd: &Path,
+) -> anyhow::Result<BTreeMap<CheckerShell, PathBuf>> {
+    let shells = shells.into_iter().collect::<BTreeSet<_>>();
 
-    let outcome = rt.spawn(async move {
-        let num_targets = targets.len();
+    shells
+        .into_iter()
+        .map(|shell| Ok((shell, shell.find(cwd)?)))
+        .collect()
+}
 
-        let (ctrl_c_tx, ctrl_c_rx) = tokio::sync::broadcast::channel(cmp::max(1, num_targets));
+fn judge_in_parallel<T, C, F, O>(
+    draw_target: ProgressDrawTarget,
+    ctrl_c: fn() -> C,
+    targets: Vec<T>,
+    name: fn(&T) -> Option<&str>,
+    settings: &JudgeSettings,
+    judge_one: F,
+) -> anyhow::Result<JudgeOutcome>
+where
+    T: 'static + Clone + Send,
+    C: 'static + Future<Output = tokio::io::Result<()>> + Send,
+    F: 'static + Fn(usize, T, broadcast::Receiver<String>) -> O + Send + Sync,
+    O: 'static + Future<Output = anyhow::Result<Verdict>> + Send,
+{
+    let num_targets = targets.len();
+
+    let quoted_name_width = targets
+        .iter()
+        .flat_map(name)
+        .map(|s| format!("{:?}", s).width())
+        .max()
+        .unwrap_or(0);
+
+    let mp = MultiProgress::with_draw_target(draw_target);
+
+    let targets = targets
+        .into_iter()
+        .enumerate()
+        .map(|(i, target)| {
+            let pb = mp.add(ProgressBar::new_spinner());
+
+            pb.set_style(progress_style("{prefix}{spinner} {msg:bold}"));
+
+            pb.set_prefix(&format!(
+                "{}/{} ({} ",
+                align_right(&(i + 1).to_string(), num_targets.to_string().len()),
+                num_targets,
+                align_left(
+                    &format!("{:?})", name(&target).unwrap_or("")),
+                    quoted_name_width + 1,
+                ),
+            ));
+
+            pb.set_message("Judging...");
+            pb.enable_steady_tick(50);
+
+            (target, pb)
+        })
+        .collect::<Vec<_>>();
+
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_io()
+        .enable_time()
+        .build()?;
+
+    let judge_one = Arc::new(judge_one);
+    let jobs = settings.jobs();
+    let warm_up = settings.warm_up();
+
+    let outcome = rt.spawn(async move {
+        let (ctrl_c_tx, ctrl_c_rx) = broadcast::channel(cmp::max(1, num_targets));
+        let warm_up_ctrl_c_rx = ctrl_c_tx.subscribe();
 
         let mut ctrl_c_rxs = iter::once(ctrl_c_rx)
             .chain(iter::repeat_with(|| ctrl_c_tx.subscribe()))
@@ -511,155 +1190,35 @@ pub fn judge<C: 'static + Future<Output = tokio::io::Result<()>> + Send>(
             ctrl_c_tx.send(err_msg).unwrap();
         });
 
-        let (job_start_tx, mut job_start_rx) = tokio::sync::mpsc::channel(num_cpus::get());
-        for _ in 0..num_cpus::get() {
+        if let (true, Some((target, pb))) = (warm_up, targets.first()) {
+            tokio::task::block_in_place(|| pb.set_message("Warming up..."));
+            judge_one(0, target.clone(), warm_up_ctrl_c_rx).await?;
+            tokio::task::block_in_place(|| pb.set_message("Judging..."));
+        }
+
+        let (job_start_tx, mut job_start_rx) = tokio::sync::mpsc::channel(jobs);
+        for _ in 0..jobs {
             job_start_tx.send(()).await?;
         }
 
         let mut results = vec![];
 
-        for (i, (test_case, pb)) in targets.into_iter().enumerate() {
-            let cmd = cmd.clone();
-            let stdin_path = tempdir_path.join(format!("{}-stdin", i));
-            let actual_stdout_path = tempdir_path.join(format!("{}-actual-stdout", i));
-            let expected_stdout_path = tempdir_path.join(format!("{}-expected-stdout", i));
-            let stderr_path = tempdir_path.join(format!("{}-stderr", i));
-            let bash_exe = bash_exe.clone();
-
+        for (i, (target, pb)) in targets.into_iter().enumerate() {
             job_start_rx.recv().await;
 
             let job_start_tx = job_start_tx.clone();
-            let mut ctrl_c_rx = ctrl_c_rxs.pop().expect("should have enough length");
-            let pb_clone = pb.clone();
+            let ctrl_c_rx = ctrl_c_rxs.pop().expect("should have enough length");
+            let judge_one = judge_one.clone();
 
             results.push(tokio::task::spawn(async move {
-                let result = tokio::task::spawn(async move {
-                    tokio::fs::write(&stdin_path, test_case.input.as_ref()).await?;
-
-                    let test_case_name = test_case.name.clone();
-                    let timelimit = test_case.timelimit;
-                    let stdin = test_case.input.clone();
-                    let expected = test_case.output.clone();
-
-                    let cwd = &cmd.cwd;
-                    let cmd = cmd
-                        .build(
-                            (stdin.len() >= 10 * 1024).then(|| &*stdin_path),
-                            &actual_stdout_path,
-                            &stderr_path,
-                        )
-                        .await?;
-
-                    let started = Instant::now();
-
-                    let mut child = { cmd }.spawn()?;
-
-                    if let Some(mut child_stdin) = child.stdin.take() {
-                        child_stdin.write_all((*stdin).as_ref()).await?;
-                    }
-
-                    macro_rules! with_ctrl_c {
-                        ($future:expr) => {
-                            select! {
-                                __output = $future => __output,
-                                err_msg = ctrl_c_rx.recv().fuse() => {
-                                    let _ = child.kill();
-                                    bail!("{}", err_msg?);
-                                },
-                            }
-                        };
-                    }
-
-                    let status = if let Some(timelimit) = timelimit {
-                        let timeout = timelimit + Duration::from_millis(100);
-
-                        if let Ok(status) =
-                            with_ctrl_c!(tokio::time::timeout(timeout, child.wait()).fuse())
-                        {
-                            status?
-                        } else {
-                            let _ = child.kill().await;
-                            let verdict = Verdict::TimelimitExceeded {
-                                test_case_name,
-                                timelimit,
-                                stdin,
-                                expected,
-                            };
-                            tokio::task::block_in_place(|| {
-                                pb_clone.set_style(progress_style(&format!(
-                                    "{{prefix}}{{msg:{}}}",
-                                    verdict.summary_style(),
-                                )));
-                                pb_clone.finish_with_message(&verdict.summary());
-                            });
-                            return Ok(verdict);
-                        }
-                    } else {
-                        with_ctrl_c!(child.wait().fuse())?
-                    };
-
-                    let elapsed = Instant::now() - started;
-
-                    let stdout = utf8(tokio::fs::read(&actual_stdout_path).await?)?;
-                    let stderr = utf8(tokio::fs::read(&stderr_path).await?)?;
-
-                    if matches!(timelimit, Some(t) if t < elapsed) {
-                        Ok(Verdict::TimelimitExceeded {
-                            test_case_name,
-                            timelimit: timelimit.unwrap(),
-                            stdin,
-                            expected,
-                        })
-                    } else if !status.success() {
-                        Ok(Verdict::RuntimeError {
-                            test_case_name,
-                            elapsed,
-                            stdin,
-                            stdout,
-                            stderr,
-                            expected,
-                            status,
-                        })
-                    } else if let Err((checker_stdout, checker_stderr, note)) = check(
-                        &test_case.output,
-                        &stdout,
-                        cwd,
-                        &stdin_path,
-                        &actual_stdout_path,
-                        &expected_stdout_path,
-                        &bash_exe,
-                    )
-                    .await?
-                    {
-                        Ok(Verdict::WrongAnswer {
-                            test_case_name,
-                            elapsed,
-                            stdin,
-                            stdout,
-                            stderr,
-                            checker_stdout,
-                            checker_stderr,
-                            expected,
-                            note,
-                        })
-                    } else {
-                        Ok(Verdict::Accepted {
-                            test_case_name,
-                            elapsed,
-                            stdin,
-                            stdout,
-                            stderr,
-                            expected,
-                        })
-                    }
-                })
-                .await
-                .unwrap();
+                let result = tokio::task::spawn(judge_one(i, target, ctrl_c_rx))
+                    .await
+                    .unwrap();
 
                 match &result {
                     Ok(verdict) => {
                         tokio::task::block_in_place(|| {
-                            pb.set_style(progress_style(&format!(
+                            pb.set_style(progress_style(format!(
                                 "{{prefix}}{{msg:{}}}",
                                 verdict.summary_style(),
                             )));
@@ -692,9 +1251,7 @@ pub fn judge<C: 'static + Future<Output = tokio::io::Result<()>> + Send>(
 
     mp.join()?;
 
-    let outcome = rt.block_on(outcome)??;
-    tempdir.close()?;
-    return Ok(outcome);
+    return rt.block_on(outcome)?;
 
     fn progress_style(template: impl AsRef<str>) -> ProgressStyle {
         ProgressStyle::default_spinner().template(template.as_ref())
@@ -711,60 +1268,977 @@ pub fn judge<C: 'static + Future<Output = tokio::io::Result<()>> + Send>(
     }
 }
 
+async fn judge_batch_test_case(
+    i: usize,
+    test_case: BatchTestCase,
+    cmd: &CommandExpression,
+    cpu: Option<usize>,
+    checkers: &BatchCheckers,
+    tempdir: &Path,
+    mut ctrl_c_rx: broadcast::Receiver<String>,
+) -> anyhow::Result<Verdict> {
+    let stdin_path = tempdir.join(format!("{}-stdin", i));
+    let actual_stdout_path = tempdir.join(format!("{}-actual-stdout", i));
+    let expected_stdout_path = tempdir.join(format!("{}-expected-stdout", i));
+    let stderr_path = tempdir.join(format!("{}-stderr", i));
+
+    tokio::fs::write(&stdin_path, test_case.input.as_ref()).await?;
+
+    let test_case_name = test_case.name.clone();
+    let timelimit = test_case.timelimit;
+    let stdin = test_case.input.clone();
+    let expected = test_case.output.clone();
+
+    if let Some(validator) = &test_case.validator {
+        match validate(validator, &cmd.cwd, &stdin_path, &checkers.shells).await? {
+            ValidateOutcome::Valid => {}
+            ValidateOutcome::Invalid {
+                resource_usage,
+                validator_stdout,
+                validator_stderr,
+                reason,
+            } => {
+                return Ok(Verdict::InvalidInput {
+                    test_case_name,
+                    resource_usage,
+                    stdin,
+                    validator_stdout,
+                    validator_stderr,
+                    expected,
+                    reason,
+                    transcript: None,
+                });
+            }
+            ValidateOutcome::Failed {
+                resource_usage,
+                validator_stdout,
+                validator_stderr,
+                reason,
+            } => {
+                return Ok(Verdict::CheckerFailed {
+                    test_case_name,
+                    resource_usage,
+                    stdin,
+                    stdout: Arc::from(""),
+                    stderr: Arc::from(""),
+                    checker_stdout: validator_stdout,
+                    checker_stderr: validator_stderr,
+                    expected,
+                    reason,
+                    transcript: None,
+                });
+            }
+        }
+    }
+
+    let memory_limit = test_case.memory_limit;
+    let timelimit_mode = test_case.timelimit_mode;
+
+    let cwd = &cmd.cwd;
+    let mut cmd = cmd.build(
+        open_file(&stdin_path).await?.into(),
+        create_file(&actual_stdout_path).await?.into(),
+        create_file(&stderr_path).await?.into(),
+    );
+    if let Some(cpu) = cpu {
+        process::pin_to_cpu(&mut cmd, cpu)?;
+    }
+    if let Some(memory_limit) = memory_limit {
+        process::limit_memory(&mut cmd, memory_limit.bytes())?;
+    }
+
+    let timeout = timelimit.map(|t| wall_timeout(t, timelimit_mode));
+
+    let process::Finished {
+        status,
+        usage: resource_usage,
+        killed,
+    } = or_ctrl_c(Process::spawn(&mut cmd)?.wait(timeout), &mut ctrl_c_rx).await??;
+
+    if killed == Some(process::Killed::Timeout)
+        || matches!(timelimit, Some(t) if t < resource_usage.time(timelimit_mode))
+    {
+        return Ok(Verdict::TimelimitExceeded {
+            test_case_name,
+            timelimit: timelimit.unwrap(),
+            resource_usage,
+            stdin,
+            expected,
+            transcript: None,
+        });
+    }
+
+    let stdout = utf8(tokio::fs::read(&actual_stdout_path).await?)?;
+    let stderr = utf8(tokio::fs::read(&stderr_path).await?)?;
+
+    if resource_usage.exceeds_memory_limit(memory_limit, status) {
+        Ok(Verdict::MemoryLimitExceeded {
+            test_case_name,
+            memory_limit: memory_limit.unwrap(),
+            resource_usage,
+            stdin,
+            stdout,
+            stderr,
+            expected,
+            transcript: None,
+        })
+    } else if !status.success() {
+        Ok(Verdict::RuntimeError {
+            test_case_name,
+            resource_usage,
+            stdin,
+            stdout,
+            stderr,
+            expected,
+            status,
+            transcript: None,
+        })
+    } else {
+        let outcome = check(
+            &test_case,
+            &stdout,
+            cwd,
+            &stdin_path,
+            &actual_stdout_path,
+            &expected_stdout_path,
+            checkers,
+        )
+        .await?;
+
+        Ok(match outcome {
+            CheckOutcome::Accepted {
+                checker_stdout,
+                checker_stderr,
+            } => Verdict::Accepted {
+                test_case_name,
+                resource_usage,
+                stdin,
+                stdout,
+                stderr,
+                checker_stdout,
+                checker_stderr,
+                expected,
+                transcript: None,
+            },
+            CheckOutcome::WrongAnswer {
+                checker_stdout,
+                checker_stderr,
+                notes,
+            } => Verdict::WrongAnswer {
+                test_case_name,
+                resource_usage,
+                stdin,
+                stdout,
+                stderr,
+                checker_stdout,
+                checker_stderr,
+                expected,
+                notes,
+                transcript: None,
+            },
+            CheckOutcome::PresentationError {
+                checker_stdout,
+                checker_stderr,
+            } => Verdict::PresentationError {
+                test_case_name,
+                resource_usage,
+                stdin,
+                stdout,
+                stderr,
+                checker_stdout,
+                checker_stderr,
+                expected,
+                transcript: None,
+            },
+            CheckOutcome::CheckerFailed {
+                checker_stdout,
+                checker_stderr,
+                reason,
+            } => Verdict::CheckerFailed {
+                test_case_name,
+                resource_usage,
+                stdin,
+                stdout,
+                stderr,
+                checker_stdout,
+                checker_stderr,
+                expected,
+                reason,
+                transcript: None,
+            },
+        })
+    }
+}
+
+async fn judge_interactive_test_case(
+    i: usize,
+    test_case: InteractiveTestCase,
+    cmd: &CommandExpression,
+    cpu: Option<usize>,
+    shells: &BTreeMap<CheckerShell, PathBuf>,
+    tempdir: &Path,
+    mut ctrl_c_rx: broadcast::Receiver<String>,
+) -> anyhow::Result<Verdict> {
+    let input_path = tempdir.join(format!("{}-input", i));
+    let interactor_output_path = tempdir.join(format!("{}-interactor-output", i));
+    let stderr_path = tempdir.join(format!("{}-stderr", i));
+
+    let InteractiveTestCase {
+        name: test_case_name,
+        timelimit,
+        memory_limit,
+        timelimit_mode,
+        input: stdin,
+        interactor,
+    } = test_case;
+
+    let expected = ExpectedOutput::Deterministic(DeterministicExpectedOutput::Pass);
+
+    tokio::fs::write(&input_path, stdin.as_ref()).await?;
+
+    let protocol = interactor.protocol;
+    let args = match protocol {
+        CheckerProtocol::Env => vec![],
+        CheckerProtocol::Testlib => vec![&*input_path, &*interactor_output_path],
+    };
+
+    let interactor = checker_command(shells, &interactor.cmd, interactor.shell, &cmd.cwd, &args)
+        .and_then(|mut cmd| {
+            cmd.env("INPUT", &input_path)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped());
+            Process::spawn(&mut cmd).map_err(Into::into)
+        });
+
+    let mut interactor = match interactor {
+        Ok(interactor) => interactor,
+        Err(err) => {
+            return Ok(Verdict::CheckerFailed {
+                test_case_name,
+                resource_usage: ResourceUsage::default(),
+                stdin,
+                stdout: Arc::from(""),
+                stderr: Arc::from(""),
+                checker_stdout: Arc::from(""),
+                checker_stderr: Arc::from(""),
+                expected,
+                reason: format!("could not start the interactor: {}", err),
+                transcript: None,
+            });
+        }
+    };
+
+    let mut cmd = cmd.build(
+        Stdio::piped(),
+        Stdio::piped(),
+        create_file(&stderr_path).await?.into(),
+    );
+    if let Some(cpu) = cpu {
+        process::pin_to_cpu(&mut cmd, cpu)?;
+    }
+    if let Some(memory_limit) = memory_limit {
+        process::limit_memory(&mut cmd, memory_limit.bytes())?;
+    }
+
+    let mut solution = Process::spawn(&mut cmd)?;
+    let started = solution.started;
+
+    let solution_stdout = tokio::task::spawn(forward(
+        solution.stdout.take().expect("specified `Stdio::piped()`"),
+        interactor.stdin.take().expect("specified `Stdio::piped()`"),
+        started,
+    ));
+    let interactor_stdout = tokio::task::spawn(forward(
+        interactor
+            .stdout
+            .take()
+            .expect("specified `Stdio::piped()`"),
+        solution.stdin.take().expect("specified `Stdio::piped()`"),
+        started,
+    ));
+    let interactor_stderr = read_to_end(interactor.stderr.take());
+
+    let timeout = timelimit.map(|t| wall_timeout(t, timelimit_mode));
+
+    let (solution, interactor) = or_ctrl_c(
+        async {
+            let solution = solution.wait(timeout).await?;
+            let interactor = interactor.wait(timeout).await?;
+            Ok::<_, io::Error>((solution, interactor))
+        },
+        &mut ctrl_c_rx,
+    )
+    .await??;
+
+    let process::Finished {
+        status: solution_status,
+        usage: resource_usage,
+        killed,
+    } = solution;
+
+    let solution_stdout = solution_stdout.await??;
+    let stdout = utf8(
+        solution_stdout
+            .iter()
+            .flat_map(|(_, s)| s)
+            .copied()
+            .collect(),
+    )?;
+    let stderr = utf8(tokio::fs::read(&stderr_path).await?)?;
+    let checker_stderr = utf8_lossy(&interactor_stderr.await??);
+    let transcript = Some(Transcript::new(solution_stdout, interactor_stdout.await??));
+
+    // The solution is judged before the interactor, which usually fails as well when the
+    // solution crashes or times out.
+    if killed == Some(process::Killed::Timeout)
+        || matches!(timelimit, Some(t) if t < resource_usage.time(timelimit_mode))
+    {
+        return Ok(Verdict::TimelimitExceeded {
+            test_case_name,
+            timelimit: timelimit.unwrap(),
+            resource_usage,
+            stdin,
+            expected,
+            transcript,
+        });
+    }
+    if resource_usage.exceeds_memory_limit(memory_limit, solution_status) {
+        return Ok(Verdict::MemoryLimitExceeded {
+            test_case_name,
+            memory_limit: memory_limit.unwrap(),
+            resource_usage,
+            stdin,
+            stdout,
+            stderr,
+            expected,
+            transcript,
+        });
+    }
+    if !solution_status.success() {
+        return Ok(Verdict::RuntimeError {
+            test_case_name,
+            resource_usage,
+            stdin,
+            stdout,
+            stderr,
+            expected,
+            status: solution_status,
+            transcript,
+        });
+    }
+
+    let outcome = if let Some(process::Killed::Timeout) = interactor.killed {
+        CheckOutcome::CheckerFailed {
+            checker_stdout: Arc::from(""),
+            checker_stderr,
+            reason: "interactor timed out".to_owned(),
+        }
+    } else {
+        checker_outcome(
+            "interactor",
+            protocol,
+            interactor.status,
+            Arc::from(""),
+            checker_stderr,
+        )
+    };
+
+    Ok(match outcome {
+        CheckOutcome::Accepted {
+            checker_stdout,
+            checker_stderr,
+        } => Verdict::Accepted {
+            test_case_name,
+            resource_usage,
+            stdin,
+            stdout,
+            stderr,
+            checker_stdout,
+            checker_stderr,
+            expected,
+            transcript,
+        },
+        CheckOutcome::WrongAnswer {
+            checker_stdout,
+            checker_stderr,
+            notes,
+        } => Verdict::WrongAnswer {
+            test_case_name,
+            resource_usage,
+            stdin,
+            stdout,
+            stderr,
+            checker_stdout,
+            checker_stderr,
+            expected,
+            notes,
+            transcript,
+        },
+        CheckOutcome::PresentationError {
+            checker_stdout,
+            checker_stderr,
+        } => Verdict::PresentationError {
+            test_case_name,
+            resource_usage,
+            stdin,
+            stdout,
+            stderr,
+            checker_stdout,
+            checker_stderr,
+            expected,
+            transcript,
+        },
+        CheckOutcome::CheckerFailed {
+            checker_stdout,
+            checker_stderr,
+            reason,
+        } => Verdict::CheckerFailed {
+            test_case_name,
+            resource_usage,
+            stdin,
+            stdout,
+            stderr,
+            checker_stdout,
+            checker_stderr,
+            expected,
+            reason,
+            transcript,
+        },
+    })
+}
+
+/// Returns the wall time after which a solution with the time limit `timelimit` is killed.
+///
+/// In the CPU time mode, a solution that is not using CPU (e.g. waiting for stdin) is still
+/// killed at twice the time limit in wall time.
+fn wall_timeout(timelimit: Duration, mode: TimelimitMode) -> Duration {
+    match mode {
+        TimelimitMode::WallTime => timelimit + Duration::from_millis(100),
+        TimelimitMode::CpuTime => 2 * timelimit + Duration::from_millis(100),
+    }
+}
+
+/// Copies `src` to `dst` until `src` reaches EOF, returning the copied bytes split into lines
+/// with the time each line was read.
+///
+/// If `dst` is closed on the other side, `src` is still read to the end.
+async fn forward(
+    mut src: impl AsyncRead + Unpin,
+    dst: impl AsyncWrite + Unpin,
+    started: Instant,
+) -> io::Result<Vec<(Duration, Vec<u8>)>> {
+    let mut dst = Some(dst);
+    let mut lines = vec![];
+    let mut line = vec![];
+    let mut elapsed = Duration::default();
+    let mut buf = [0; 4096];
+
+    loop {
+        let n = src.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        elapsed = Instant::now() - started;
+
+        for &b in &buf[..n] {
+            line.push(b);
+            if b == b'\n' {
+                lines.push((elapsed, mem::take(&mut line)));
+            }
+        }
+
+        if let Some(w) = &mut dst {
+            if let Err(err) = async {
+                w.write_all(&buf[..n]).await?;
+                w.flush().await
+            }
+            .await
+            {
+                if err.kind() != io::ErrorKind::BrokenPipe {
+                    return Err(err);
+                }
+                dst = None;
+            }
+        }
+    }
+
+    if !line.is_empty() {
+        lines.push((elapsed, line));
+    }
+    Ok(lines)
+}
+
+async fn or_ctrl_c<T>(
+    future: impl Future<Output = T>,
+    ctrl_c_rx: &mut broadcast::Receiver<String>,
+) -> anyhow::Result<T> {
+    select! {
+        output = future.fuse() => Ok(output),
+        err_msg = ctrl_c_rx.recv().fuse() => bail!("{}", err_msg?),
+    }
+}
+
+/// Forwards the result of a `ctrl_c` function to receivers, including ones that subscribe after
+/// it is sent.
+#[derive(Clone)]
+struct CtrlC {
+    tx: broadcast::Sender<String>,
+    received: Arc<Mutex<Option<String>>>,
+}
+
+impl CtrlC {
+    fn spawn<C: 'static + Future<Output = tokio::io::Result<()>> + Send>(
+        ctrl_c: fn() -> C,
+    ) -> Self {
+        let (tx, _) = broadcast::channel(1);
+        let received = Arc::new(Mutex::new(None));
+
+        tokio::task::spawn({
+            let (tx, received) = (tx.clone(), received.clone());
+            async move {
+                let err_msg = match ctrl_c().await {
+                    Ok(()) => "Recieved Ctrl-c".to_owned(),
+                    Err(err) => err.to_string(),
+                };
+                *received.lock().unwrap() = Some(err_msg.clone());
+                let _ = tx.send(err_msg);
+            }
+        });
+
+        Self { tx, received }
+    }
+
+    /// Subscribes, or fails if the message has already been sent.
+    fn subscribe(&self) -> anyhow::Result<broadcast::Receiver<String>> {
+        let rx = self.tx.subscribe();
+        if let Some(err_msg) = &*self.received.lock().unwrap() {
+            bail!("{}", err_msg);
+        }
+        Ok(rx)
+    }
+}
+
+/// Runs `cmd` and returns what it wrote to `stdout_path`. Fails if `cmd` exits unsuccessfully or
+/// runs longer than `timelimit`.
+async fn run(
+    cmd: &CommandExpression,
+    stdin: Stdio,
+    stdout_path: &Path,
+    timelimit: Option<Duration>,
+    ctrl_c_rx: &mut broadcast::Receiver<String>,
+) -> anyhow::Result<Arc<str>> {
+    let mut cmd = cmd.build(
+        stdin,
+        create_file(stdout_path).await?.into(),
+        Stdio::piped(),
+    );
+
+    let mut process = Process::spawn(&mut cmd)?;
+    let stderr = read_to_end(process.stderr.take());
+    let process::Finished { status, killed, .. } =
+        or_ctrl_c(process.wait(timelimit), ctrl_c_rx).await??;
+    let stderr = utf8_lossy(&stderr.await??);
+
+    if let (Some(process::Killed::Timeout), Some(timelimit)) = (killed, timelimit) {
+        bail!(
+            "timed out ({} ms)\nstderr:\n{}",
+            timelimit.as_millis(),
+            stderr
+        );
+    }
+    ensure!(status.success(), "{}\nstderr:\n{}", status, stderr);
+    utf8(tokio::fs::read(stdout_path).await?)
+}
+
+async fn open_file(path: &Path) -> io::Result<std::fs::File> {
+    Ok(tokio::fs::File::open(path).await?.into_std().await)
+}
+
+async fn create_file(path: &Path) -> io::Result<std::fs::File> {
+    Ok(tokio::fs::File::create(path).await?.into_std().await)
+}
+
+/// Builds a command that runs `cmd` in `cwd`.
+///
+/// `args` are passed to `cmd`. If `cmd` is a script, they are available as `"$@"` at the end of
+/// it.
+fn checker_command(
+    shells: &BTreeMap<CheckerShell, PathBuf>,
+    cmd: &CheckerCommand,
+    shell: Option<CheckerShell>,
+    cwd: &Path,
+    args: &[&Path],
+) -> anyhow::Result<std::process::Command> {
+    let mut command = match cmd {
+        CheckerCommand::Script(script) => {
+            let shell = cmd.shell(shell).expect("should be `Some` for scripts");
+            let program = &shells[&shell];
+            let mut command = std::process::Command::new(program);
+            command.arg("-c");
+            if args.is_empty() {
+                command.arg(script);
+            } else {
+                command.arg(format!("{} \"$@\"", script)).arg(program);
+            }
+            command
+        }
+        CheckerCommand::Argv(argv) => {
+            ensure!(
+                shell.is_none(),
+                "`shell` cannot be specified for a command in the list form",
+            );
+            let (program, args) = argv.split_first().with_context(|| "empty command")?;
+            let program = which::which_in(program, env::var_os("PATH"), cwd)
+                .map_err(|_| anyhow!("`{}` not found", program))?;
+            let mut command = std::process::Command::new(program);
+            command.args(args);
+            command
+        }
+    };
+    command.args(args).current_dir(cwd);
+    Ok(command)
+}
+
+enum CheckOutcome {
+    Accepted {
+        checker_stdout: Arc<str>,
+        checker_stderr: Arc<str>,
+    },
+    WrongAnswer {
+        checker_stdout: Arc<str>,
+        checker_stderr: Arc<str>,
+        notes: Vec<WrongAnswerNote>,
+    },
+    PresentationError {
+        checker_stdout: Arc<str>,
+        checker_stderr: Arc<str>,
+    },
+    CheckerFailed {
+        checker_stdout: Arc<str>,
+        checker_stderr: Arc<str>,
+        reason: String,
+    },
+}
+
 async fn check(
-    expected: &ExpectedOutput,
+    test_case: &BatchTestCase,
     actual: &str,
     cwd: &Path,
     stdin_path: &Path,
     actual_stdout_path: &Path,
     expected_stdout_path: &Path,
-    bash_exe: &Path,
-) -> anyhow::Result<Result<(), (Arc<str>, Arc<str>, Option<WrongAnswerNote>)>> {
-    match expected {
-        ExpectedOutput::Deterministic(expected) => Ok(if expected.accepts(actual) {
-            Ok(())
-        } else {
-            let note = expected
-                .expected_stdout()
-                .filter(|expected| expected.split_whitespace().eq(actual.split_whitespace()))
-                .map(|_| WrongAnswerNote::WordsMatched);
-            Err((Arc::from(""), Arc::from(""), note))
-        }),
-        ExpectedOutput::Checker { text, cmd, shell } => {
-            let (program, args) = match shell {
-                CheckerShell::Bash => (bash_exe, [OsStr::new("-c"), OsStr::new(cmd)]),
-            };
+    checkers: &BatchCheckers,
+) -> anyhow::Result<CheckOutcome> {
+    match &test_case.output {
+        ExpectedOutput::Custom { text, name } => {
+            let checker = checkers.custom.get(name).cloned();
+            let checker = checker.with_context(|| format!("no such checker: {:?}", name))?;
+            let normalize = test_case.normalize;
+            let input = test_case.input.clone();
+            let text = text
+                .as_deref()
+                .map(|text| normalize.apply(text).into_owned());
+            let actual = normalize.apply(actual).into_owned();
+            let result = tokio::task::spawn_blocking(move || {
+                checker.check(&input, text.as_deref(), &actual)
+            })
+            .await;
+
+            let empty = || Arc::from("");
+            Ok(match result {
+                Ok(CheckResult::Accepted) => CheckOutcome::Accepted {
+                    checker_stdout: empty(),
+                    checker_stderr: empty(),
+                },
+                Ok(CheckResult::WrongAnswer { message }) => CheckOutcome::WrongAnswer {
+                    checker_stdout: empty(),
+                    checker_stderr: message.into(),
+                    notes: vec![],
+                },
+                Ok(CheckResult::PresentationError { message }) => CheckOutcome::PresentationError {
+                    checker_stdout: empty(),
+                    checker_stderr: message.into(),
+                },
+                Ok(CheckResult::Failed { reason }) => CheckOutcome::CheckerFailed {
+                    checker_stdout: empty(),
+                    checker_stderr: empty(),
+                    reason,
+                },
+                Err(err) if err.is_panic() => CheckOutcome::CheckerFailed {
+                    checker_stdout: empty(),
+                    checker_stderr: empty(),
+                    reason: "checker panicked".to_owned(),
+                },
+                Err(err) => return Err(err.into()),
+            })
+        }
+        ExpectedOutput::Deterministic(_) | ExpectedOutput::AnyOf(_) => {
+            let candidates = test_case
+                .output
+                .candidates()
+                .iter()
+                .map(|c| c.normalized(test_case.normalize))
+                .collect::<Vec<_>>();
+            let actual = &*test_case.normalize.apply(actual);
+
+            Ok(if candidates.iter().any(|c| c.accepts(actual)) {
+                CheckOutcome::Accepted {
+                    checker_stdout: Arc::from(""),
+                    checker_stderr: Arc::from(""),
+                }
+            } else if test_case.presentation_error
+                && candidates.iter().any(|c| {
+                    matches!(
+                        c.expected_stdout(),
+                        Some(expected) if expected.split_whitespace().eq(actual.split_whitespace())
+                    )
+                })
+            {
+                CheckOutcome::PresentationError {
+                    checker_stdout: Arc::from(""),
+                    checker_stderr: Arc::from(""),
+                }
+            } else {
+                CheckOutcome::WrongAnswer {
+                    checker_stdout: Arc::from(""),
+                    checker_stderr: Arc::from(""),
+                    notes: diff::closest(&candidates, actual)
+                        .map(|expected| note::diagnose(expected, actual))
+                        .unwrap_or_default(),
+                }
+            })
+        }
+        ExpectedOutput::Checker {
+            text,
+            cmd,
+            shell,
+            protocol,
+            timelimit,
+        } => {
+            // The checker reads the outputs from the files, so they are normalized there.
+            if let Cow::Owned(actual) = test_case.normalize.apply(actual) {
+                tokio::fs::write(actual_stdout_path, actual).await?;
+            }
 
             let mut env_vars = vec![("INPUT", stdin_path), ("ACTUAL_OUTPUT", actual_stdout_path)];
             if let Some(text) = text {
+                let text = test_case.normalize.apply(text);
                 tokio::fs::write(expected_stdout_path, text.as_ref()).await?;
                 env_vars.push(("EXPECTED_OUTPUT", expected_stdout_path));
+            } else if *protocol == CheckerProtocol::Testlib {
+                tokio::fs::write(expected_stdout_path, "").await?;
+            }
+
+            let args = match protocol {
+                CheckerProtocol::Env => vec![],
+                CheckerProtocol::Testlib => {
+                    vec![stdin_path, actual_stdout_path, expected_stdout_path]
+                }
+            };
+
+            let checker =
+                checker_command(&checkers.shells, cmd, *shell, cwd, &args).and_then(|mut cmd| {
+                    cmd.envs(env_vars)
+                        .stdin(Stdio::null())
+                        .stdout(Stdio::piped())
+                        .stderr(Stdio::piped());
+                    Process::spawn(&mut cmd).map_err(Into::into)
+                });
+
+            let mut checker = match checker {
+                Ok(checker) => checker,
+                Err(err) => {
+                    return Ok(CheckOutcome::CheckerFailed {
+                        checker_stdout: Arc::from(""),
+                        checker_stderr: Arc::from(""),
+                        reason: format!("could not start the checker: {}", err),
+                    });
+                }
+            };
+
+            let checker_stdout = read_to_end(checker.stdout.take());
+            let checker_stderr = read_to_end(checker.stderr.take());
+
+            let timelimit = timelimit.unwrap_or(DEFAULT_CHECKER_TIMELIMIT);
+
+            let process::Finished { status, killed, .. } = checker.wait(Some(timelimit)).await?;
+
+            let checker_stdout = utf8_lossy(&checker_stdout.await??);
+            let checker_stderr = utf8_lossy(&checker_stderr.await??);
+
+            if killed.is_some() {
+                return Ok(CheckOutcome::CheckerFailed {
+                    checker_stdout,
+                    checker_stderr,
+                    reason: format!("checker timed out ({} ms)", timelimit.as_millis()),
+                });
             }
 
-            let Output {
+            Ok(checker_outcome(
+                "checker",
+                *protocol,
                 status,
-                stdout,
-                stderr,
-            } = tokio::process::Command::new(program)
-                .args(&args)
-                .envs(env_vars)
-                .current_dir(cwd)
-                .stdin(Stdio::null())
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .kill_on_drop(true)
-                .output()
-                .await?;
+                checker_stdout,
+                checker_stderr,
+            ))
+        }
+    }
+}
 
-            let (stdout, stderr) = (utf8(stdout)?, utf8(stderr)?);
+/// Interprets the exit status of a checker or an interactor named `name`.
+fn checker_outcome(
+    name: &str,
+    protocol: CheckerProtocol,
+    status: ExitStatus,
+    checker_stdout: Arc<str>,
+    checker_stderr: Arc<str>,
+) -> CheckOutcome {
+    match (protocol, status.code()) {
+        (_, None) => CheckOutcome::CheckerFailed {
+            checker_stdout,
+            checker_stderr,
+            reason: format!("{} {}", name, status),
+        },
+        // `_points` means that the output is correct and is given a score, as in problems with
+        // partial scores. Since verdicts have no scores, it is treated as Accepted, and the score
+        // is left in the checker output.
+        (_, Some(0)) | (CheckerProtocol::Testlib, Some(7)) => CheckOutcome::Accepted {
+            checker_stdout,
+            checker_stderr,
+        },
+        (CheckerProtocol::Env, _) | (CheckerProtocol::Testlib, Some(1)) => {
+            CheckOutcome::WrongAnswer {
+                checker_stdout,
+                checker_stderr,
+                notes: vec![],
+            }
+        }
+        // partially correct (`_pc(n)` in testlib)
+        (CheckerProtocol::Testlib, Some(code)) if code >= 16 => CheckOutcome::WrongAnswer {
+            checker_stdout,
+            checker_stderr,
+            notes: vec![],
+        },
+        (CheckerProtocol::Testlib, Some(2)) => CheckOutcome::PresentationError {
+            checker_stdout,
+            checker_stderr,
+        },
+        (CheckerProtocol::Testlib, _) => CheckOutcome::CheckerFailed {
+            checker_stdout,
+            checker_stderr,
+            reason: format!("{} {}", name, status),
+        },
+    }
+}
 
-            Ok(if status.success() {
-                Ok(())
-            } else {
-                Err((stdout, stderr, None))
-            })
+enum ValidateOutcome {
+    Valid,
+    Invalid {
+        resource_usage: ResourceUsage,
+        validator_stdout: Arc<str>,
+        validator_stderr: Arc<str>,
+        reason: String,
+    },
+    Failed {
+        resource_usage: ResourceUsage,
+        validator_stdout: Arc<str>,
+        validator_stderr: Arc<str>,
+        reason: String,
+    },
+}
+
+/// Runs `validator` on the input at `stdin_path`.
+///
+/// The command of `validator` must have been resolved in `BatchCheckers::new`.
+async fn validate(
+    validator: &Validator,
+    cwd: &Path,
+    stdin_path: &Path,
+    shells: &BTreeMap<CheckerShell, PathBuf>,
+) -> anyhow::Result<ValidateOutcome> {
+    let Validator {
+        cmd,
+        shell,
+        protocol,
+        timelimit,
+    } = validator;
+
+    let mut cmd = checker_command(shells, cmd, *shell, cwd, &[])?;
+    cmd.env("INPUT", stdin_path)
+        .stdin(open_file(stdin_path).await?)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let mut validator = match Process::spawn(&mut cmd) {
+        Ok(validator) => validator,
+        Err(err) => {
+            return Ok(ValidateOutcome::Failed {
+                resource_usage: ResourceUsage::default(),
+                validator_stdout: Arc::from(""),
+                validator_stderr: Arc::from(""),
+                reason: format!("could not start the validator: {}", err),
+            });
         }
+    };
+
+    let validator_stdout = read_to_end(validator.stdout.take());
+    let validator_stderr = read_to_end(validator.stderr.take());
+
+    let timelimit = timelimit.unwrap_or(DEFAULT_CHECKER_TIMELIMIT);
+
+    let process::Finished {
+        status,
+        usage: resource_usage,
+        killed,
+    } = validator.wait(Some(timelimit)).await?;
+
+    let validator_stdout = utf8_lossy(&validator_stdout.await??);
+    let validator_stderr = utf8_lossy(&validator_stderr.await??);
+
+    let failed = |reason| ValidateOutcome::Failed {
+        resource_usage,
+        validator_stdout: validator_stdout.clone(),
+        validator_stderr: validator_stderr.clone(),
+        reason,
+    };
+
+    if killed.is_some() {
+        return Ok(failed(format!(
+            "validator timed out ({} ms)",
+            timelimit.as_millis()
+        )));
     }
+
+    Ok(match (protocol, status.code()) {
+        (_, None) => failed(format!("validator {}", status)),
+        (_, Some(0)) => ValidateOutcome::Valid,
+        (ValidatorProtocol::ExitCode, _) | (ValidatorProtocol::Testlib, Some(3)) => {
+            ValidateOutcome::Invalid {
+                resource_usage,
+                validator_stdout,
+                validator_stderr,
+                reason: format!("validator {}", status),
+            }
+        }
+        (ValidatorProtocol::Testlib, _) => failed(format!("validator {}", status)),
+    })
+}
+
+fn read_to_end(
+    rdr: Option<impl 'static + AsyncRead + Unpin + Send>,
+) -> tokio::task::JoinHandle<io::Result<Vec<u8>>> {
+    tokio::task::spawn(async move {
+        let mut buf = vec![];
+        if let Some(mut rdr) = rdr {
+            rdr.read_to_end(&mut buf).await?;
+        }
+        Ok(buf)
+    })
+}
+
+fn utf8_lossy(bytes: &[u8]) -> Arc<str> {
+    String::from_utf8_lossy(bytes).into()
 }
 
 fn utf8(bytes: Vec<u8>) -> anyhow::Result<Arc<str>> {
@@ -772,3 +2246,1055 @@ fn utf8(bytes: Vec<u8>) -> anyhow::Result<Arc<str>> {
         .map(Into::into)
         .map_err(|_| anyhow!("the output was not a valid UTF-8 string"))
 }
+
+mod serde_fn {
+    pub(super) mod arc_str {
+        use serde::{Deserialize, Deserializer, Serializer};
+        use std::sync::Arc;
+
+        pub(crate) fn serialize<S>(this: &Arc<str>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serializer.serialize_str(this)
+        }
+
+        pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Arc<str>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            String::deserialize(deserializer).map(Into::into)
+        }
+    }
+
+    pub(super) mod duration_secs {
+        use serde::{de::Error as _, Deserialize, Deserializer, Serializer};
+        use std::time::Duration;
+
+        pub(crate) fn serialize<S>(this: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serializer.serialize_f64(this.as_secs_f64())
+        }
+
+        pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let secs = f64::deserialize(deserializer)?;
+            if !(secs.is_finite() && secs >= 0.0) {
+                return Err(D::Error::custom(format!("invalid duration: {}", secs)));
+            }
+            Ok(Duration::from_secs_f64(secs))
+        }
+    }
+
+    pub(super) mod option_duration_secs {
+        use serde::{Deserialize, Deserializer, Serializer};
+        use std::time::Duration;
+
+        pub(crate) fn serialize<S>(
+            this: &Option<Duration>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            if let Some(duration) = this {
+                serializer.serialize_some(&duration.as_secs_f64())
+            } else {
+                serializer.serialize_none()
+            }
+        }
+
+        pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            #[derive(Deserialize)]
+            struct Secs(#[serde(with = "super::duration_secs")] Duration);
+
+            Option::<Secs>::deserialize(deserializer).map(|s| s.map(|Secs(d)| d))
+        }
+    }
+
+    /// Serializes an `ExitStatus` as `{ "code": .., "signal": .. }`.
+    pub(super) mod exit_status {
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+        use std::process::ExitStatus;
+
+        #[derive(Deserialize, Serialize)]
+        struct Repr {
+            code: Option<i32>,
+            #[serde(default)]
+            signal: Option<i32>,
+        }
+
+        pub(crate) fn serialize<S>(this: &ExitStatus, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            #[cfg(unix)]
+            let signal = std::os::unix::process::ExitStatusExt::signal(this);
+            #[cfg(not(unix))]
+            let signal = None;
+
+            Repr {
+                code: this.code(),
+                signal,
+            }
+            .serialize(serializer)
+        }
+
+        #[cfg(unix)]
+        pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<ExitStatus, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            use serde::de::Error as _;
+            use std::os::unix::process::ExitStatusExt as _;
+
+            match Repr::deserialize(deserializer)? {
+                Repr {
+                    signal: Some(signal),
+                    ..
+                } => Ok(ExitStatus::from_raw(signal & 0x7f)),
+                Repr {
+                    code: Some(code), ..
+                } => Ok(ExitStatus::from_raw((code & 0xff) << 8)),
+                Repr { .. } => Err(D::Error::custom("either `code` or `signal` is required")),
+            }
+        }
+
+        #[cfg(windows)]
+        pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<ExitStatus, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            use serde::de::Error as _;
+            use std::os::windows::process::ExitStatusExt as _;
+
+            let code = Repr::deserialize(deserializer)?
+                .code
+                .ok_or_else(|| D::Error::custom("`code` is required"))?;
+            Ok(ExitStatus::from_raw(code as u32))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        checker_command, judge, judge_interactive, BatchCheckers, CheckResult, CommandExpression,
+        Direction, JudgeOutcome, JudgeSettings, PreciseTiming, Transcript, Verdict,
+    };
+    use bikecleats_testsuite::{
+        BatchTestSuite, CheckerCommand, CheckerShell, InteractiveTestSuite,
+    };
+    use futures_util::future::{self, Pending};
+    use indicatif::ProgressDrawTarget;
+    use std::{
+        collections::{BTreeMap, HashSet},
+        env,
+        num::NonZeroUsize,
+        path::Path,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    #[cfg(unix)]
+    use std::time::Duration;
+
+    fn never() -> Pending<tokio::io::Result<()>> {
+        future::pending()
+    }
+
+    fn sh(script: &str) -> anyhow::Result<CommandExpression> {
+        Ok(CommandExpression {
+            program: "sh".into(),
+            args: vec!["-c".into(), script.into()],
+            cwd: env::current_dir()?,
+            env: Default::default(),
+        })
+    }
+
+    /// Judges `solution`, a script run with `sh`, on the test cases of `suite`.
+    fn judge_script(
+        solution: &str,
+        suite: &str,
+        settings: &JudgeSettings,
+    ) -> anyhow::Result<JudgeOutcome> {
+        let suite = serde_yaml::from_str::<BatchTestSuite>(suite)?;
+        let test_cases =
+            suite.load_test_cases(Path::new(""), None::<HashSet<String>>, |_| Ok(vec![]))?;
+        judge(
+            ProgressDrawTarget::hidden(),
+            never,
+            &sh(solution)?,
+            &test_cases,
+            settings,
+        )
+    }
+
+    /// Judges `solution`, a script run with `sh`, on the test cases of `suite`, an interactive
+    /// test suite.
+    fn judge_interactive_script(solution: &str, suite: &str) -> anyhow::Result<JudgeOutcome> {
+        let suite = serde_yaml::from_str::<InteractiveTestSuite>(suite)?;
+        let test_cases = suite.load_test_cases(None::<HashSet<String>>)?;
+        judge_interactive(
+            ProgressDrawTarget::hidden(),
+            never,
+            &sh(solution)?,
+            &test_cases,
+            &JudgeSettings::default(),
+        )
+    }
+
+    #[test]
+    fn custom_checker() -> anyhow::Result<()> {
+        static SUITE: &str = r#"
+timelimit: 10s
+match:
+  Custom:
+    name: sum
+cases:
+  - in: "1 2\n"
+"#;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut settings = JudgeSettings::default();
+        settings.checkers.register("sum", {
+            let calls = calls.clone();
+            move |input: &str, _: Option<&str>, actual: &str| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                let sum = input
+                    .split_whitespace()
+                    .map(|s| s.parse::<i64>().unwrap())
+                    .sum::<i64>();
+                if actual.trim() == sum.to_string() {
+                    CheckResult::Accepted
+                } else {
+                    CheckResult::WrongAnswer {
+                        message: format!("expected {}", sum),
+                    }
+                }
+            }
+        });
+
+        let outcome = judge_script("echo 3", SUITE, &settings)?;
+        assert!(matches!(outcome.verdicts[0], Verdict::Accepted { .. }));
+        let outcome = judge_script("echo 4", SUITE, &settings)?;
+        assert!(matches!(
+            &outcome.verdicts[0],
+            Verdict::WrongAnswer { checker_stderr, .. } if &**checker_stderr == "expected 3"
+        ));
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+
+        settings
+            .checkers
+            .register("sum", |_: &str, _: Option<&str>, _: &str| -> CheckResult {
+                panic!()
+            });
+        let outcome = judge_script("echo 3", SUITE, &settings)?;
+        assert!(matches!(
+            &outcome.verdicts[0],
+            Verdict::CheckerFailed { reason, .. } if reason == "checker panicked"
+        ));
+
+        assert!(judge_script("echo 3", SUITE, &JudgeSettings::default()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn presentation_error() -> anyhow::Result<()> {
+        let suite = |presentation_error: bool| {
+            format!(
+                r#"
+timelimit: 10s
+presentation_error: {}
+match: Exact
+cases:
+  - in: ""
+    out: "1 2\n"
+"#,
+                presentation_error,
+            )
+        };
+        let settings = JudgeSettings::default();
+
+        let outcome = judge_script("echo ' 1  2'", &suite(true), &settings)?;
+        assert!(matches!(
+            outcome.verdicts[0],
+            Verdict::PresentationError { .. }
+        ));
+        assert!(outcome.error_on_fail(true).is_ok());
+        assert!(outcome.error_on_fail(false).is_err());
+
+        let outcome = judge_script("echo ' 1  2'", &suite(false), &settings)?;
+        assert!(matches!(outcome.verdicts[0], Verdict::WrongAnswer { .. }));
+        assert!(outcome.error_on_fail(true).is_err());
+
+        let outcome = judge_script("echo '1 3'", &suite(true), &settings)?;
+        assert!(matches!(outcome.verdicts[0], Verdict::WrongAnswer { .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn validator() -> anyhow::Result<()> {
+        let suite = |validator: &str| {
+            format!(
+                r#"
+timelimit: 10s
+match: Lines
+validator:
+  {}
+cases:
+  - in: "1\n"
+    out: "1\n"
+"#,
+                validator,
+            )
+        };
+        let judge = |validator: &str| {
+            let outcome = judge_script("cat", &suite(validator), &JudgeSettings::default())?;
+            Ok::<_, anyhow::Error>(outcome.verdicts.into_iter().next().unwrap())
+        };
+
+        assert!(matches!(judge("cmd: exit 0")?, Verdict::Accepted { .. }));
+        assert!(matches!(
+            judge("cmd: exit 1")?,
+            Verdict::InvalidInput { reason, .. } if reason == "validator exit status: 1"
+        ));
+        assert!(matches!(
+            judge("{ cmd: exit 3, protocol: Testlib }")?,
+            Verdict::InvalidInput { .. }
+        ));
+        assert!(matches!(
+            judge("{ cmd: exit 1, protocol: Testlib }")?,
+            Verdict::CheckerFailed { .. }
+        ));
+        assert!(matches!(
+            judge("cmd: kill -9 $$")?,
+            Verdict::CheckerFailed { .. }
+        ));
+        assert!(matches!(
+            judge("{ cmd: sleep 10, timelimit: 100ms }")?,
+            Verdict::CheckerFailed { reason, .. } if reason == "validator timed out (100 ms)"
+        ));
+        assert!(judge("cmd: [./no-such-validator]").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn jobs() -> anyhow::Result<()> {
+        static SUITE: &str = r#"
+timelimit: 5s
+match: Lines
+cases:
+  - in: ""
+    out: ""
+  - in: ""
+    out: ""
+  - in: ""
+    out: ""
+"#;
+        let tempdir = tempfile::Builder::new()
+            .prefix("bikecleats-judge-test-")
+            .tempdir()?;
+        let all_accepted = |outcome: JudgeOutcome| {
+            outcome
+                .verdicts
+                .iter()
+                .all(|v| matches!(v, Verdict::Accepted { .. }))
+        };
+
+        // Fails if another test case is running.
+        let exclusive = format!(
+            "mkdir '{0}' && sleep 0.1 && rmdir '{0}'",
+            tempdir.path().join("lock").display(),
+        );
+        let settings = JudgeSettings {
+            jobs: NonZeroUsize::new(1),
+            ..JudgeSettings::default()
+        };
+        assert!(all_accepted(judge_script(&exclusive, SUITE, &settings)?));
+        let settings = JudgeSettings {
+            jobs: NonZeroUsize::new(3),
+            precise_timing: Some(PreciseTiming::default()),
+            ..JudgeSettings::default()
+        };
+        assert!(all_accepted(judge_script(&exclusive, SUITE, &settings)?));
+
+        // Waits until every test case is running.
+        let barrier = format!(
+            "touch '{0}/'$$; while [ $(ls '{0}' | wc -l) -lt 3 ]; do sleep 0.01; done",
+            tempdir.path().display(),
+        );
+        let settings = JudgeSettings {
+            jobs: NonZeroUsize::new(3),
+            ..JudgeSettings::default()
+        };
+        assert!(all_accepted(judge_script(&barrier, SUITE, &settings)?));
+        Ok(())
+    }
+
+    #[test]
+    fn warm_up() -> anyhow::Result<()> {
+        static SUITE: &str = r#"
+timelimit: 5s
+match: Lines
+cases:
+  - in: ""
+    out: ""
+  - in: ""
+    out: ""
+"#;
+        let tempdir = tempfile::Builder::new()
+            .prefix("bikecleats-judge-test-")
+            .tempdir()?;
+        let runs = |warm_up: bool| {
+            let path = tempdir.path().join(format!("{}", warm_up));
+            let settings = JudgeSettings {
+                precise_timing: Some(PreciseTiming { warm_up, cpu: None }),
+                ..JudgeSettings::default()
+            };
+            let solution = format!("echo >> '{}'", path.display());
+            let outcome = judge_script(&solution, SUITE, &settings)?;
+            assert!(outcome
+                .verdicts
+                .iter()
+                .all(|v| matches!(v, Verdict::Accepted { .. })));
+            Ok::<_, anyhow::Error>(std::fs::read_to_string(path)?.lines().count())
+        };
+
+        assert_eq!(2, runs(false)?);
+        // The warm-up run is not reported.
+        assert_eq!(3, runs(true)?);
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn pin_to_cpu() -> anyhow::Result<()> {
+        let cpu = (0..libc::CPU_SETSIZE as usize)
+            .find(|&cpu| crate::process::is_cpu_available(cpu).unwrap())
+            .unwrap();
+        let settings = |cpu| JudgeSettings {
+            precise_timing: Some(PreciseTiming {
+                warm_up: false,
+                cpu: Some(cpu),
+            }),
+            ..JudgeSettings::default()
+        };
+        let suite = format!(
+            r#"
+timelimit: 5s
+match: Lines
+cases:
+  - in: ""
+    out: "{}\n"
+"#,
+            cpu,
+        );
+
+        let outcome = judge_script(
+            "awk '/^Cpus_allowed_list:/ { print $2 }' /proc/self/status",
+            &suite,
+            &settings(cpu),
+        )?;
+        assert!(matches!(outcome.verdicts[0], Verdict::Accepted { .. }));
+
+        let err = judge_script("true", &suite, &settings(libc::CPU_SETSIZE as _)).unwrap_err();
+        assert_eq!(
+            format!("CPU {} is not available to this process", libc::CPU_SETSIZE),
+            err.to_string(),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn timelimit_mode() -> anyhow::Result<()> {
+        let suite = |timelimit_mode: &str| {
+            format!(
+                r#"
+timelimit: 500ms
+timelimit_mode: {}
+match: Lines
+cases:
+  - in: ""
+    out: ""
+"#,
+                timelimit_mode,
+            )
+        };
+        let judge = |solution: &str, timelimit_mode: &str| {
+            let outcome =
+                judge_script(solution, &suite(timelimit_mode), &JudgeSettings::default())?;
+            Ok::<_, anyhow::Error>(outcome.verdicts.into_iter().next().unwrap())
+        };
+
+        // Sleeping takes no CPU time.
+        assert!(matches!(
+            judge("sleep 0.8", "CpuTime")?,
+            Verdict::Accepted { .. }
+        ));
+        assert!(matches!(
+            judge("sleep 0.8", "WallTime")?,
+            Verdict::TimelimitExceeded { .. }
+        ));
+        assert!(matches!(
+            judge("while :; do :; done", "CpuTime")?,
+            Verdict::TimelimitExceeded { .. }
+        ));
+        Ok(())
+    }
+
+    // `RLIMIT_DATA` is not enforced on macOS.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn memory_limit() -> anyhow::Result<()> {
+        static SUITE: &str = r#"
+timelimit: 10s
+memory_limit: 64 MiB
+match: Lines
+cases:
+  - in: ""
+    out: ""
+"#;
+        let judge = |solution: &str| {
+            let outcome = judge_script(solution, SUITE, &JudgeSettings::default())?;
+            Ok::<_, anyhow::Error>(outcome.verdicts.into_iter().next().unwrap())
+        };
+
+        assert!(matches!(judge("true")?, Verdict::Accepted { .. }));
+        assert!(matches!(judge("exit 1")?, Verdict::RuntimeError { .. }));
+        // The peak RSS exceeds the limit.
+        assert!(matches!(
+            judge("awk 'BEGIN { while (1) a[i++] = i }'")?,
+            Verdict::MemoryLimitExceeded { .. }
+        ));
+        // Doubling the buffer fails before the peak RSS reaches the limit.
+        assert!(matches!(
+            judge("awk 'BEGIN { s = \"x\"; while (1) s = s s }'")?,
+            Verdict::MemoryLimitExceeded { .. }
+        ));
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn interactive_memory_limit() -> anyhow::Result<()> {
+        static SUITE: &str = r#"
+timelimit: 10s
+memory_limit: 64 MiB
+interactor:
+  cmd: [cat]
+cases:
+  - in: ""
+"#;
+
+        let outcome = judge_interactive_script("true", SUITE)?;
+        assert!(matches!(outcome.verdicts[0], Verdict::Accepted { .. }));
+        let outcome = judge_interactive_script("awk 'BEGIN { while (1) a[i++] = i }'", SUITE)?;
+        assert!(matches!(
+            outcome.verdicts[0],
+            Verdict::MemoryLimitExceeded { .. }
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn interactive() -> anyhow::Result<()> {
+        static SUITE: &str = r#"
+timelimit: 1s
+interactor:
+  cmd: 'read n < "$INPUT"; echo "$n"; read a; test "$a" = $((n * 2))'
+  shell: Sh
+cases:
+  - name: "3"
+    in: "3\n"
+  - name: "5"
+    in: "5\n"
+"#;
+        let messages = |transcript: &Transcript| {
+            transcript
+                .messages
+                .iter()
+                .map(|m| (m.direction, m.text.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        let outcome = judge_interactive_script("read n; echo $((n * 2))", SUITE)?;
+        assert!(outcome.error_on_fail(false).is_ok());
+        assert_eq!(
+            vec![
+                (Direction::InteractorToSolution, "5\n".to_owned()),
+                (Direction::SolutionToInteractor, "10\n".to_owned()),
+            ],
+            messages(outcome.verdicts[1].transcript().unwrap()),
+        );
+        assert_eq!(Some("10\n"), outcome.verdicts[1].stdout());
+
+        let outcome = judge_interactive_script("read n; echo $((n + 3))", SUITE)?;
+        assert!(matches!(outcome.verdicts[0], Verdict::Accepted { .. }));
+        assert!(matches!(
+            &outcome.verdicts[1],
+            Verdict::WrongAnswer { transcript: Some(transcript), .. }
+                if messages(transcript)
+                    == [
+                        (Direction::InteractorToSolution, "5\n".to_owned()),
+                        (Direction::SolutionToInteractor, "8\n".to_owned()),
+                    ]
+        ));
+
+        let outcome = judge_interactive_script("read n; echo crashed >&2; exit 3", SUITE)?;
+        assert!(matches!(
+            &outcome.verdicts[0],
+            Verdict::RuntimeError { status, stderr, transcript: Some(transcript), .. }
+                if status.code() == Some(3)
+                    && &**stderr == "crashed\n"
+                    && messages(transcript)
+                        == [(Direction::InteractorToSolution, "3\n".to_owned())]
+        ));
+
+        let outcome = judge_interactive_script("read n; sleep 10", SUITE)?;
+        assert!(matches!(
+            &outcome.verdicts[0],
+            Verdict::TimelimitExceeded { transcript: Some(transcript), .. }
+                if messages(transcript)
+                    == [(Direction::InteractorToSolution, "3\n".to_owned())]
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn interactive_verdict_order() -> anyhow::Result<()> {
+        // Fails when the solution does not answer.
+        static SUITE: &str = r#"
+timelimit: 10s
+interactor:
+  cmd: 'read n < "$INPUT"; echo "$n"; read a; test "$a" = $((n * 2))'
+  shell: Sh
+cases:
+  - in: "3\n"
+"#;
+        let judge = |solution: &str| {
+            let outcome = judge_interactive_script(solution, SUITE)?;
+            Ok::<_, anyhow::Error>(outcome.verdicts.into_iter().next().unwrap())
+        };
+
+        assert!(matches!(judge("exit 1")?, Verdict::RuntimeError { .. }));
+        assert!(matches!(judge("kill -9 $$")?, Verdict::RuntimeError { .. }));
+        assert!(matches!(judge("read n")?, Verdict::WrongAnswer { .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn interactive_testlib() -> anyhow::Result<()> {
+        // Exits with the code that the solution answers.
+        static SUITE: &str = r#"
+timelimit: 10s
+interactor:
+  cmd: 'cat "$1"; read code; exit "$code"'
+  shell: Sh
+  protocol: Testlib
+cases:
+  - in: "3\n"
+"#;
+        let judge = |code: i32| {
+            let solution = format!("read n && echo {}", code);
+            let outcome = judge_interactive_script(&solution, SUITE)?;
+            Ok::<_, anyhow::Error>(outcome.verdicts.into_iter().next().unwrap())
+        };
+
+        assert!(matches!(judge(0)?, Verdict::Accepted { .. }));
+        assert!(matches!(judge(1)?, Verdict::WrongAnswer { .. }));
+        assert!(matches!(judge(2)?, Verdict::PresentationError { .. }));
+        assert!(matches!(
+            judge(3)?,
+            Verdict::CheckerFailed { reason, .. } if reason == "interactor exit status: 3"
+        ));
+        assert!(matches!(judge(7)?, Verdict::Accepted { .. }));
+        assert!(matches!(judge(16)?, Verdict::WrongAnswer { .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn interactive_timelimit_mode() -> anyhow::Result<()> {
+        let suite = |timelimit_mode: &str| {
+            format!(
+                r#"
+timelimit: 500ms
+timelimit_mode: {}
+interactor:
+  cmd: 'read n < "$INPUT"; echo "$n"; read a; test "$a" = $((n * 2))'
+  shell: Sh
+cases:
+  - in: "3\n"
+"#,
+                timelimit_mode,
+            )
+        };
+        let solution = "read n; sleep 0.8; echo $((n * 2))";
+
+        let outcome = judge_interactive_script(solution, &suite("CpuTime"))?;
+        assert!(matches!(outcome.verdicts[0], Verdict::Accepted { .. }));
+
+        let outcome = judge_interactive_script(solution, &suite("WallTime"))?;
+        assert!(matches!(
+            outcome.verdicts[0],
+            Verdict::TimelimitExceeded { .. }
+        ));
+
+        let outcome = judge_interactive_script("while :; do :; done", &suite("CpuTime"))?;
+        assert!(matches!(
+            outcome.verdicts[0],
+            Verdict::TimelimitExceeded { .. }
+        ));
+        Ok(())
+    }
+
+    /// Returns a script that starts a background process, which creates `path` after a second
+    /// unless it is killed.
+    #[cfg(unix)]
+    fn spawn_background_process(path: &Path) -> String {
+        format!("(sleep 1; touch '{}') &", path.display())
+    }
+
+    /// Asserts that the process started by `spawn_background_process(path)` was killed.
+    #[cfg(unix)]
+    fn assert_background_process_killed(path: &Path) {
+        std::thread::sleep(Duration::from_millis(1500));
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn kill_process_group() -> anyhow::Result<()> {
+        static SUITE: &str = r#"
+timelimit: 300ms
+match: Lines
+cases:
+  - in: ""
+    out: ""
+"#;
+
+        let tempdir = tempfile::Builder::new()
+            .prefix("bikecleats-judge-test-")
+            .tempdir()?;
+        let path = tempdir.path().join("alive");
+
+        let solution = format!("{} sleep 100", spawn_background_process(&path));
+        let started = std::time::Instant::now();
+        let outcome = judge_script(&solution, SUITE, &JudgeSettings::default())?;
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(matches!(
+            outcome.verdicts[0],
+            Verdict::TimelimitExceeded { .. }
+        ));
+        assert_background_process_killed(&path);
+
+        // The group is killed even if the solution itself exits in time.
+        let solution = format!("{} true", spawn_background_process(&path));
+        let outcome = judge_script(&solution, SUITE, &JudgeSettings::default())?;
+        assert!(matches!(outcome.verdicts[0], Verdict::Accepted { .. }));
+        assert_background_process_killed(&path);
+        Ok(())
+    }
+
+    #[test]
+    fn testlib_checker() -> anyhow::Result<()> {
+        let judge = |checker: &str| {
+            let suite = format!(
+                r#"
+timelimit: 10s
+match:
+  Checker:
+    cmd: [sh, -c, "{}"]
+    protocol: Testlib
+cases:
+  - in: ""
+    out: ""
+"#,
+                checker,
+            );
+            let outcome = judge_script("true", &suite, &JudgeSettings::default())?;
+            Ok::<_, anyhow::Error>(outcome.verdicts.into_iter().next().unwrap())
+        };
+
+        assert!(matches!(judge("exit 0")?, Verdict::Accepted { .. }));
+        assert!(matches!(judge("exit 1")?, Verdict::WrongAnswer { .. }));
+        assert!(matches!(
+            judge("exit 2")?,
+            Verdict::PresentationError { .. }
+        ));
+        assert!(matches!(
+            judge("exit 3")?,
+            Verdict::CheckerFailed { reason, .. } if reason == "checker exit status: 3"
+        ));
+        assert!(matches!(
+            judge("echo 0.5; exit 7")?,
+            Verdict::Accepted { checker_stdout, .. } if &*checker_stdout == "0.5\n"
+        ));
+        assert!(matches!(judge("exit 16")?, Verdict::WrongAnswer { .. }));
+        assert!(matches!(judge("exit 4")?, Verdict::CheckerFailed { .. }));
+        assert!(matches!(
+            judge("kill -9 $$")?,
+            Verdict::CheckerFailed { .. }
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn checker_command_argv() -> anyhow::Result<()> {
+        let cmd = CheckerCommand::Argv(
+            [
+                "sh",
+                "-c",
+                r#"printf '%s|' "$@""#,
+                "sh",
+                "a  b",
+                "$HOME",
+                "*",
+            ]
+            .iter()
+            .map(|&s| s.to_owned())
+            .collect(),
+        );
+        let output = checker_command(
+            &BTreeMap::new(),
+            &cmd,
+            None,
+            &env::current_dir()?,
+            &[Path::new("c d")],
+        )?
+        .output()?;
+        assert_eq!("a  b|$HOME|*|c d|", String::from_utf8(output.stdout)?);
+        Ok(())
+    }
+
+    #[test]
+    fn find_only_used_shells() -> anyhow::Result<()> {
+        let shells = |checker: &str, validator: &str| {
+            let suite = format!(
+                r#"
+timelimit: 10s
+match:
+  Checker:
+    {}
+validator:
+  {}
+cases:
+  - in: ""
+    out: ""
+"#,
+                checker, validator,
+            );
+            let suite = serde_yaml::from_str::<BatchTestSuite>(&suite)?;
+            let test_cases =
+                suite.load_test_cases(Path::new(""), None::<HashSet<String>>, |_| Ok(vec![]))?;
+            let checkers = BatchCheckers::new(&test_cases, &Default::default(), Path::new(""))?;
+            Ok::<_, anyhow::Error>(checkers.shells.keys().copied().collect::<Vec<_>>())
+        };
+
+        assert_eq!(
+            Vec::<CheckerShell>::new(),
+            shells("cmd: [sh, -c, exit 0]", "cmd: [sh, -c, exit 0]")?,
+        );
+        assert_eq!(
+            vec![CheckerShell::Sh],
+            shells("{ cmd: exit 0, shell: Sh }", "cmd: [sh, -c, exit 0]")?,
+        );
+        assert_eq!(
+            vec![CheckerShell::Bash, CheckerShell::Sh],
+            shells("{ cmd: exit 0, shell: Sh }", "cmd: exit 0")?,
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn checker_timelimit() -> anyhow::Result<()> {
+        let tempdir = tempfile::Builder::new()
+            .prefix("bikecleats-judge-test-")
+            .tempdir()?;
+        let path = tempdir.path().join("alive");
+
+        let suite = format!(
+            r#"
+timelimit: 10s
+match:
+  Checker:
+    cmd: [sh, -c, "{} sleep 100"]
+    timelimit: 300ms
+cases:
+  - in: ""
+    out: ""
+"#,
+            spawn_background_process(&path),
+        );
+        let started = std::time::Instant::now();
+        let outcome = judge_script("true", &suite, &JudgeSettings::default())?;
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(matches!(
+            &outcome.verdicts[0],
+            Verdict::CheckerFailed { reason, .. } if reason == "checker timed out (300 ms)"
+        ));
+        assert_background_process_killed(&path);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_json() -> anyhow::Result<()> {
+        use crate::{ResourceUsage, TranscriptMessage};
+        use bikecleats_testsuite::{DeterministicExpectedOutput, ExpectedOutput};
+        use std::{os::unix::process::ExitStatusExt as _, process::ExitStatus};
+
+        static EXPECTED: &str = r#"{
+  "verdicts": [
+    {
+      "verdict": "RuntimeError",
+      "test_case_name": "killed",
+      "resource_usage": {
+        "wall": 1.5,
+        "user": 0.25,
+        "sys": null,
+        "max_rss": null
+      },
+      "stdin": "1\n",
+      "stdout": "",
+      "stderr": "",
+      "expected": {
+        "Deterministic": {
+          "Exact": {
+            "text": "2\n"
+          }
+        }
+      },
+      "status": {
+        "code": null,
+        "signal": 9
+      },
+      "transcript": null
+    },
+    {
+      "verdict": "TimelimitExceeded",
+      "test_case_name": null,
+      "timelimit": 1.5,
+      "resource_usage": {
+        "wall": 1.5,
+        "user": 0.25,
+        "sys": null,
+        "max_rss": null
+      },
+      "stdin": "1\n",
+      "expected": {
+        "Deterministic": {
+          "Exact": {
+            "text": "2\n"
+          }
+        }
+      },
+      "transcript": null
+    },
+    {
+      "verdict": "Accepted",
+      "test_case_name": "interactive",
+      "resource_usage": {
+        "wall": 1.5,
+        "user": 0.25,
+        "sys": null,
+        "max_rss": null
+      },
+      "stdin": "",
+      "stdout": "",
+      "stderr": "",
+      "checker_stdout": "",
+      "checker_stderr": "",
+      "expected": {
+        "Deterministic": {
+          "Exact": {
+            "text": "2\n"
+          }
+        }
+      },
+      "transcript": {
+        "messages": [
+          {
+            "elapsed": 0.5,
+            "direction": "InteractorToSolution",
+            "text": "1\n"
+          }
+        ]
+      }
+    }
+  ]
+}
+"#;
+
+        let expected = || {
+            ExpectedOutput::Deterministic(DeterministicExpectedOutput::Exact { text: "2\n".into() })
+        };
+        let resource_usage = ResourceUsage {
+            wall: Duration::from_millis(1500),
+            user: Some(Duration::from_millis(250)),
+            sys: None,
+            max_rss: None,
+        };
+        let outcome = JudgeOutcome {
+            verdicts: vec![
+                Verdict::RuntimeError {
+                    test_case_name: Some("killed".to_owned()),
+                    resource_usage,
+                    stdin: Arc::from("1\n"),
+                    stdout: Arc::from(""),
+                    stderr: Arc::from(""),
+                    expected: expected(),
+                    status: ExitStatus::from_raw(9),
+                    transcript: None,
+                },
+                Verdict::TimelimitExceeded {
+                    test_case_name: None,
+                    timelimit: Duration::from_millis(1500),
+                    resource_usage,
+                    stdin: Arc::from("1\n"),
+                    expected: expected(),
+                    transcript: None,
+                },
+                Verdict::Accepted {
+                    test_case_name: Some("interactive".to_owned()),
+                    resource_usage,
+                    stdin: Arc::from(""),
+                    stdout: Arc::from(""),
+                    stderr: Arc::from(""),
+                    checker_stdout: Arc::from(""),
+                    checker_stderr: Arc::from(""),
+                    expected: expected(),
+                    transcript: Some(Transcript {
+                        messages: vec![TranscriptMessage {
+                            elapsed: Duration::from_millis(500),
+                            direction: Direction::InteractorToSolution,
+                            text: Arc::from("1\n"),
+                        }],
+                    }),
+                },
+            ],
+        };
+
+        let mut json = vec![];
+        outcome.write_json(&mut json)?;
+        let json = String::from_utf8(json)?;
+        assert_eq!(EXPECTED, json);
+
+        // The signal is restored on deserialization.
+        let outcome = serde_json::from_str::<JudgeOutcome>(&json)?;
+        let mut reserialized = vec![];
+        outcome.write_json(&mut reserialized)?;
+        assert_eq!(EXPECTED, String::from_utf8(reserialized)?);
+        Ok(())
+    }
+}
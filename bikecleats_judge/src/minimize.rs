@@ -0,0 +1,372 @@
+//! Minimization of failing inputs.
+
+use crate::{
+    judge_batch_test_case, open_file, run, BatchCheckers, Checkers, CommandExpression, CtrlC,
+    JudgeOutcome, Verdict,
+};
+use anyhow::bail;
+use bikecleats_testsuite::{BatchTestCase, DEFAULT_GENERATOR_TIMELIMIT};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::{
+    cmp,
+    future::Future,
+    mem,
+    path::{Path, PathBuf},
+    slice,
+    time::Duration,
+};
+
+/// Settings for [`minimize`].
+///
+/// Inputs rejected by the `validator` of the test case are not kept.
+#[derive(Debug, Clone, Default)]
+pub struct MinimizeSettings {
+    /// Prints the expected output for an input.
+    ///
+    /// If `Some`, the expected output is recomputed for each input, and an input is kept while
+    /// the solution is not accepted. Otherwise, the expected output of the test case is kept, and
+    /// an input is kept while the verdict is of the same kind.
+    pub reference: Option<CommandExpression>,
+    /// The time limit for each run of `reference`. Defaults to [`DEFAULT_GENERATOR_TIMELIMIT`].
+    pub timelimit: Option<Duration>,
+    /// Checkers referred to by `Custom` matches.
+    pub checkers: Checkers,
+}
+
+/// The smallest reproducer found by [`minimize`].
+#[derive(Debug, Clone)]
+pub struct Minimized {
+    pub test_case: BatchTestCase,
+    /// The verdict on `test_case`.
+    pub outcome: JudgeOutcome,
+}
+
+/// Shrinks the input of `test_case`, on which `cmd` fails, by removing lines and then tokens.
+pub fn minimize<C: 'static + Future<Output = tokio::io::Result<()>> + Send>(
+    draw_target: ProgressDrawTarget,
+    ctrl_c: fn() -> C,
+    cmd: &CommandExpression,
+    test_case: &BatchTestCase,
+    settings: &MinimizeSettings,
+) -> anyhow::Result<Minimized> {
+    let checkers = BatchCheckers::new(slice::from_ref(test_case), &settings.checkers, &cmd.cwd)?;
+
+    let tempdir = tempfile::Builder::new()
+        .prefix("bikecleats-minimize-")
+        .tempdir()?;
+
+    let pb = ProgressBar::new_spinner();
+    pb.set_draw_target(draw_target);
+    pb.set_style(ProgressStyle::default_spinner().template("{spinner} {msg:bold}"));
+    pb.set_message("Minimizing...");
+    pb.enable_steady_tick(50);
+
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_io()
+        .enable_time()
+        .build()?;
+
+    let minimized = rt.block_on(async {
+        let ctrl_c = CtrlC::spawn(ctrl_c);
+
+        let verdict = judge_batch_test_case(
+            0,
+            test_case.clone(),
+            cmd,
+            None,
+            &checkers,
+            tempdir.path(),
+            ctrl_c.subscribe()?,
+        )
+        .await?;
+        match &verdict {
+            Verdict::Accepted { .. } => bail!("The solution was accepted on the test case"),
+            Verdict::InvalidInput { reason, .. } => bail!("The input is invalid: {}", reason),
+            _ => {}
+        }
+
+        let mut minimizer = Minimizer {
+            cmd,
+            settings,
+            checkers: &checkers,
+            ctrl_c,
+            tempdir: tempdir.path(),
+            input_path: tempdir.path().join("minimize-input"),
+            output_path: tempdir.path().join("minimize-output"),
+            original: test_case,
+            best: (test_case.clone(), verdict),
+            pb: &pb,
+        };
+
+        let trailing_newline = test_case.input.ends_with('\n');
+        let join_lines = |lines: &[String]| {
+            let mut acc = lines.join("\n");
+            if trailing_newline && !lines.is_empty() {
+                acc += "\n";
+            }
+            acc
+        };
+
+        let mut lines = test_case.input.lines().map(ToOwned::to_owned).collect();
+        loop {
+            let len = minimizer.best.0.input.len();
+
+            lines = minimizer.remove_chunks(lines, join_lines).await?;
+
+            for i in 0..lines.len() {
+                let tokens = lines[i].split_whitespace().map(ToOwned::to_owned).collect();
+                let mut others = mem::take(&mut lines);
+                let tokens = minimizer
+                    .remove_chunks(tokens, |tokens| {
+                        others[i] = tokens.join(" ");
+                        join_lines(&others)
+                    })
+                    .await?;
+                lines = others;
+                lines[i] = tokens.join(" ");
+            }
+
+            if minimizer.best.0.input.len() == len {
+                break;
+            }
+        }
+
+        let (test_case, verdict) = minimizer.best;
+        Ok(Minimized {
+            test_case,
+            outcome: JudgeOutcome {
+                verdicts: vec![verdict],
+            },
+        })
+    });
+
+    pb.finish_and_clear();
+    tempdir.close()?;
+    minimized
+}
+
+struct Minimizer<'a> {
+    cmd: &'a CommandExpression,
+    settings: &'a MinimizeSettings,
+    checkers: &'a BatchCheckers,
+    ctrl_c: CtrlC,
+    tempdir: &'a Path,
+    input_path: PathBuf,
+    output_path: PathBuf,
+    original: &'a BatchTestCase,
+    best: (BatchTestCase, Verdict),
+    pb: &'a ProgressBar,
+}
+
+impl Minimizer<'_> {
+    /// Removes as many chunks of `units` as possible, halving the size of chunks from a half of
+    /// `units` to one.
+    async fn remove_chunks(
+        &mut self,
+        mut units: Vec<String>,
+        mut join: impl FnMut(&[String]) -> String,
+    ) -> anyhow::Result<Vec<String>> {
+        let mut chunk = cmp::max(units.len() / 2, 1);
+        loop {
+            let mut i = 0;
+            while i < units.len() {
+                let mut candidate = units.clone();
+                candidate.drain(i..cmp::min(i + chunk, units.len()));
+                if self.try_input(join(&candidate)).await? {
+                    units = candidate;
+                } else {
+                    i += chunk;
+                }
+            }
+            if chunk == 1 {
+                break Ok(units);
+            }
+            chunk /= 2;
+        }
+    }
+
+    /// Judges `input`, and keeps it if the solution still fails on it.
+    async fn try_input(&mut self, input: String) -> anyhow::Result<bool> {
+        if input.len() >= self.best.0.input.len() {
+            return Ok(false);
+        }
+        tokio::fs::write(&self.input_path, &input).await?;
+
+        let mut test_case = self.original.clone();
+        test_case.input = input.into();
+
+        if let Some(reference) = &self.settings.reference {
+            let output = run(
+                reference,
+                open_file(&self.input_path).await?.into(),
+                &self.output_path,
+                Some(
+                    self.settings
+                        .timelimit
+                        .unwrap_or(DEFAULT_GENERATOR_TIMELIMIT),
+                ),
+                &mut self.ctrl_c.subscribe()?,
+            )
+            .await;
+            match output {
+                Ok(output) => test_case.output = test_case.output.with_expected_stdout(output)?,
+                Err(_) => {
+                    // Fails if the reference was interrupted.
+                    self.ctrl_c.subscribe()?;
+                    return Ok(false);
+                }
+            }
+        }
+
+        let verdict = judge_batch_test_case(
+            0,
+            test_case.clone(),
+            self.cmd,
+            None,
+            self.checkers,
+            self.tempdir,
+            self.ctrl_c.subscribe()?,
+        )
+        .await?;
+
+        // A broken checker or validator is not what is being minimized.
+        let still_failing = if self.settings.reference.is_some() {
+            !matches!(
+                verdict,
+                Verdict::Accepted { .. }
+                    | Verdict::CheckerFailed { .. }
+                    | Verdict::InvalidInput { .. }
+            )
+        } else {
+            mem::discriminant(&verdict) == mem::discriminant(&self.best.1)
+        };
+        if still_failing {
+            tokio::task::block_in_place(|| {
+                self.pb.set_message(&format!(
+                    "Minimizing... ({} lines, {} bytes)",
+                    test_case.input.lines().count(),
+                    test_case.input.len(),
+                ));
+            });
+            self.best = (test_case, verdict);
+        }
+        Ok(still_failing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{minimize, MinimizeSettings};
+    use crate::{CheckResult, CommandExpression, Verdict};
+    use bikecleats_testsuite::BatchTestSuite;
+    use futures_util::future::{self, Pending};
+    use indicatif::ProgressDrawTarget;
+    use std::{collections::HashSet, env, path::Path};
+
+    fn never() -> Pending<tokio::io::Result<()>> {
+        future::pending()
+    }
+
+    fn sh(script: &str) -> anyhow::Result<CommandExpression> {
+        Ok(CommandExpression {
+            program: "sh".into(),
+            args: vec!["-c".into(), script.into()],
+            cwd: env::current_dir()?,
+            env: Default::default(),
+        })
+    }
+
+    /// Minimizes the only test case of `suite` on which `solution`, a script run with `sh`, fails.
+    fn minimize_script(
+        solution: &str,
+        suite: &str,
+        settings: &MinimizeSettings,
+    ) -> anyhow::Result<(String, Verdict)> {
+        let suite = serde_yaml::from_str::<BatchTestSuite>(suite)?;
+        let test_cases =
+            suite.load_test_cases(Path::new(""), None::<HashSet<String>>, |_| Ok(vec![]))?;
+        let mut minimized = minimize(
+            ProgressDrawTarget::hidden(),
+            never,
+            &sh(solution)?,
+            &test_cases[0],
+            settings,
+        )?;
+        let verdict = minimized.outcome.verdicts.pop().unwrap();
+        Ok((minimized.test_case.input.to_string(), verdict))
+    }
+
+    #[test]
+    fn keep_verdict_kind() -> anyhow::Result<()> {
+        static SUITE: &str = r#"
+timelimit: 10s
+match: Lines
+cases:
+  - in: "a b\nc x d\ne\n"
+    out: ""
+"#;
+
+        let (input, verdict) = minimize_script("! grep -q x", SUITE, &MinimizeSettings::default())?;
+        assert_eq!("x\n", input);
+        assert!(matches!(verdict, Verdict::RuntimeError { .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn recompute_expected_outputs() -> anyhow::Result<()> {
+        static SUITE: &str = r#"
+timelimit: 10s
+match: Lines
+cases:
+  - in: "1 2 3\n4 5 6 7\n8\n"
+    out: "1 2 3\n4 5 6 7\n8\n"
+"#;
+
+        let settings = MinimizeSettings {
+            reference: Some(sh("cat")?),
+            ..Default::default()
+        };
+        let (input, verdict) = minimize_script("sed 's/5 6/x/'", SUITE, &settings)?;
+        assert_eq!("5 6\n", input);
+        assert!(matches!(verdict, Verdict::WrongAnswer { .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn skip_checker_failures() -> anyhow::Result<()> {
+        static SUITE: &str = r#"
+timelimit: 10s
+match:
+  Custom:
+    name: needs-a
+cases:
+  - in: "a\nb\nc\n"
+    out: "a\nb\nc\n"
+"#;
+
+        let mut settings = MinimizeSettings {
+            reference: Some(sh("cat")?),
+            ..Default::default()
+        };
+        settings.checkers.register(
+            "needs-a",
+            |input: &str, expected: Option<&str>, actual: &str| {
+                if !input.contains('a') {
+                    CheckResult::Failed {
+                        reason: "no `a`".to_owned(),
+                    }
+                } else if Some(actual) == expected {
+                    CheckResult::Accepted
+                } else {
+                    CheckResult::WrongAnswer {
+                        message: "".to_owned(),
+                    }
+                }
+            },
+        );
+        let (input, verdict) = minimize_script("sed s/b/x/", SUITE, &settings)?;
+        assert_eq!("a\nb\n", input);
+        assert!(matches!(verdict, Verdict::WrongAnswer { .. }));
+        Ok(())
+    }
+}
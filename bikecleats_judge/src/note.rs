@@ -0,0 +1,443 @@
+//! Hints on why an output was not accepted.
+
+use bikecleats_testsuite::DeterministicExpectedOutput;
+use itertools::{EitherOrBoth, Itertools as _};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, derive_more::Display)]
+pub enum WrongAnswerNote {
+    #[display(fmt = "first mismatch at {}", _0)]
+    FirstMismatch(Position),
+    #[display(fmt = "expected {} line(s), but got {} line(s)", expected, actual)]
+    LineCount { expected: usize, actual: usize },
+    #[display(
+        fmt = "whitespace-separated words matched. try setting `match` to `SplitWhitespace`"
+    )]
+    WordsMatched,
+    #[display(fmt = "the output does not end with a newline")]
+    MissingTrailingNewline,
+    #[display(fmt = "the output has CRLF line endings")]
+    CrlfLineEndings,
+    #[display(fmt = "the output matches if letter case is ignored")]
+    CaseMismatch,
+    #[display(fmt = "the output is a prefix of the expected output")]
+    PrefixOfExpected,
+    #[display(
+        fmt = "max absolute error: {}{}",
+        max_absolute_error,
+        "max_relative_error.as_ref().map(|e| format!(\", max relative error: {}\", e)).unwrap_or_default()"
+    )]
+    FloatError {
+        max_absolute_error: FloatError,
+        /// `None` if every expected number is `0`.
+        max_relative_error: Option<FloatError>,
+    },
+}
+
+/// A 1-based position in an output.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, derive_more::Display)]
+#[display(fmt = "{}:{}", line, column)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// An error between a pair of numbers.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct FloatError {
+    pub error: f64,
+    /// The position of the number in the actual output.
+    pub position: Position,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for FloatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.3e} at {} (expected `{}`, got `{}`)",
+            self.error, self.position, self.expected, self.actual,
+        )
+    }
+}
+
+/// Diagnoses `actual`, which `expected` did not accept.
+pub(crate) fn diagnose(
+    expected: &DeterministicExpectedOutput,
+    actual: &str,
+) -> Vec<WrongAnswerNote> {
+    let text = match expected.expected_stdout() {
+        Some(text) => text,
+        None => return vec![],
+    };
+
+    let mut notes = vec![];
+
+    if let Some(position) = first_mismatch(expected, actual) {
+        notes.push(WrongAnswerNote::FirstMismatch(position));
+    }
+
+    let (expected_lines, actual_lines) = (text.lines().count(), actual.lines().count());
+    if expected_lines != actual_lines {
+        notes.push(WrongAnswerNote::LineCount {
+            expected: expected_lines,
+            actual: actual_lines,
+        });
+    }
+
+    let words_matched = text.split_whitespace().eq(actual.split_whitespace());
+    if words_matched {
+        notes.push(WrongAnswerNote::WordsMatched);
+    }
+
+    if text.ends_with('\n') && !actual.is_empty() && !actual.ends_with('\n') {
+        notes.push(WrongAnswerNote::MissingTrailingNewline);
+    }
+
+    if actual.contains("\r\n") && !text.contains("\r\n") {
+        notes.push(WrongAnswerNote::CrlfLineEndings);
+    }
+
+    if !words_matched {
+        let lowercase = |s: &str| {
+            s.split_whitespace()
+                .map(str::to_lowercase)
+                .collect::<Vec<_>>()
+        };
+        if lowercase(text) == lowercase(actual) {
+            notes.push(WrongAnswerNote::CaseMismatch);
+        }
+
+        let mut expected_words = text.split_whitespace();
+        let is_prefix = actual
+            .split_whitespace()
+            .all(|word| expected_words.next() == Some(word));
+        if is_prefix && !actual.trim().is_empty() {
+            notes.push(WrongAnswerNote::PrefixOfExpected);
+        }
+    }
+
+    if let DeterministicExpectedOutput::Float { text, .. } = expected {
+        if let Some(note) = float_error(text, actual) {
+            notes.push(note);
+        }
+    }
+
+    notes
+}
+
+fn first_mismatch(expected: &DeterministicExpectedOutput, actual: &str) -> Option<Position> {
+    match expected {
+        DeterministicExpectedOutput::Pass
+        | DeterministicExpectedOutput::UnorderedLines { .. }
+        | DeterministicExpectedOutput::UnorderedWords { .. }
+        | DeterministicExpectedOutput::Regex { .. } => None,
+        DeterministicExpectedOutput::Exact { text } => {
+            first_mismatch_in_lines(text.split_inclusive('\n'), actual.split_inclusive('\n'))
+        }
+        DeterministicExpectedOutput::Lines { text } => {
+            first_mismatch_in_lines(text.lines(), actual.lines())
+        }
+        DeterministicExpectedOutput::SplitWhitespace { text } => {
+            let actual_words = words(actual).map(|(p, w)| (p, Some(w)));
+            let mut expected_words = text.split_whitespace();
+            actual_words
+                .chain(Some((end(actual), None)))
+                .find(|&(_, word)| word != expected_words.next())
+                .map(|(position, _)| position)
+        }
+        DeterministicExpectedOutput::CaseInsensitive { text } => {
+            let actual_words = words(actual).map(|(p, w)| (p, Some(w.to_lowercase())));
+            let mut expected_words = text.split_whitespace().map(str::to_lowercase);
+            actual_words
+                .chain(Some((end(actual), None)))
+                .find(|(_, word)| *word != expected_words.next())
+                .map(|(position, _)| position)
+        }
+        DeterministicExpectedOutput::Float { text, .. }
+        | DeterministicExpectedOutput::Tokens { text, .. } => {
+            let actual_lines = actual.lines().map(Some).chain(Some(None));
+            let expected_lines = text.lines().map(Some).chain(Some(None));
+            for (i, zip) in actual_lines.zip_longest(expected_lines).enumerate() {
+                let (actual_line, expected_line) = match zip {
+                    EitherOrBoth::Both(Some(a), Some(e)) => (a, e),
+                    EitherOrBoth::Both(None, None) => return None,
+                    _ => {
+                        return Some(Position {
+                            line: i + 1,
+                            column: 1,
+                        })
+                    }
+                };
+                let mut expected_words = expected_line.split_whitespace();
+                let mismatch = words(actual_line)
+                    .map(|(p, w)| (p, Some(w)))
+                    .chain(Some((end(actual_line), None)))
+                    .enumerate()
+                    .find(
+                        |&(j, (_, actual_word))| match (expected_words.next(), actual_word) {
+                            (Some(e), Some(a)) => !expected.accepts_word_at(i + 1, j + 1, e, a),
+                            (e, a) => e != a,
+                        },
+                    );
+                if let Some((_, (position, _))) = mismatch {
+                    return Some(Position {
+                        line: i + 1,
+                        column: position.column,
+                    });
+                }
+            }
+            None
+        }
+    }
+}
+
+fn first_mismatch_in_lines<'a>(
+    expected: impl Iterator<Item = &'a str>,
+    actual: impl Iterator<Item = &'a str>,
+) -> Option<Position> {
+    expected
+        .zip_longest(actual)
+        .enumerate()
+        .find_map(|(i, zip)| {
+            let column = match zip {
+                EitherOrBoth::Both(expected, actual) if expected == actual => return None,
+                EitherOrBoth::Both(expected, actual) => {
+                    expected
+                        .chars()
+                        .zip(actual.chars())
+                        .take_while(|(c1, c2)| c1 == c2)
+                        .count()
+                        + 1
+                }
+                EitherOrBoth::Left(_) | EitherOrBoth::Right(_) => 1,
+            };
+            Some(Position {
+                line: i + 1,
+                column,
+            })
+        })
+}
+
+fn float_error(expected: &str, actual: &str) -> Option<WrongAnswerNote> {
+    let mut max_absolute_error = None::<FloatError>;
+    let mut max_relative_error = None::<FloatError>;
+
+    for (line, (expected_line, actual_line)) in expected.lines().zip(actual.lines()).enumerate() {
+        for (e, (position, a)) in expected_line.split_whitespace().zip(words(actual_line)) {
+            let (v1, v2) = match (e.parse::<f64>(), a.parse::<f64>()) {
+                (Ok(v1), Ok(v2)) if v1.is_finite() && v2.is_finite() => (v1, v2),
+                _ => continue,
+            };
+            let position = Position {
+                line: line + 1,
+                column: position.column,
+            };
+            let error = |error: f64| FloatError {
+                error,
+                position,
+                expected: e.to_owned(),
+                actual: a.to_owned(),
+            };
+
+            let (absolute_error, relative_error) =
+                DeterministicExpectedOutput::float_errors(v1, v2);
+            if !absolute_error.is_nan()
+                && !matches!(&max_absolute_error, Some(m) if absolute_error <= m.error)
+            {
+                max_absolute_error = Some(error(absolute_error));
+            }
+            // The relative error against `0` is infinite, and says nothing.
+            if relative_error.is_finite()
+                && !matches!(&max_relative_error, Some(m) if relative_error <= m.error)
+            {
+                max_relative_error = Some(error(relative_error));
+            }
+        }
+    }
+
+    Some(WrongAnswerNote::FloatError {
+        max_absolute_error: max_absolute_error.filter(|e| e.error > 0.0)?,
+        max_relative_error,
+    })
+}
+
+/// Returns the whitespace-separated words in `text` with their positions.
+fn words(text: &str) -> impl Iterator<Item = (Position, &str)> {
+    text.lines().enumerate().flat_map(|(i, line)| {
+        let mut column = 1;
+        let mut rest = line;
+        std::iter::from_fn(move || {
+            let start = rest.find(|c: char| !c.is_whitespace())?;
+            column += rest[..start].chars().count();
+            rest = &rest[start..];
+            let len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let (word, position) = (
+                &rest[..len],
+                Position {
+                    line: i + 1,
+                    column,
+                },
+            );
+            column += word.chars().count();
+            rest = &rest[len..];
+            Some((position, word))
+        })
+    })
+}
+
+/// Returns the position right after `text`.
+fn end(text: &str) -> Position {
+    if text.is_empty() || text.ends_with('\n') {
+        Position {
+            line: text.lines().count() + 1,
+            column: 1,
+        }
+    } else {
+        let last = text.lines().last().unwrap_or("");
+        Position {
+            line: text.lines().count(),
+            column: last.chars().count() + 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diagnose, end, first_mismatch, float_error, words, Position, WrongAnswerNote};
+    use bikecleats_testsuite::DeterministicExpectedOutput;
+
+    fn position(line: usize, column: usize) -> Position {
+        Position { line, column }
+    }
+
+    #[test]
+    fn words_and_end() {
+        assert_eq!(
+            vec![
+                (position(1, 2), "ab"),
+                (position(1, 6), "c"),
+                (position(2, 1), "あい"),
+                (position(2, 4), "d"),
+            ],
+            words(" ab  c\nあい d\n").collect::<Vec<_>>(),
+        );
+        assert_eq!(position(1, 1), end(""));
+        assert_eq!(position(3, 1), end("a\nb\n"));
+        assert_eq!(position(2, 4), end("a\nあいう"));
+    }
+
+    #[test]
+    fn first_mismatches() {
+        let exact = DeterministicExpectedOutput::Exact {
+            text: "abc\nあいう\n".into(),
+        };
+        assert_eq!(
+            Some(position(2, 3)),
+            first_mismatch(&exact, "abc\nあいえ\n")
+        );
+        assert_eq!(Some(position(2, 4)), first_mismatch(&exact, "abc\nあいう"));
+        assert_eq!(
+            Some(position(3, 1)),
+            first_mismatch(&exact, "abc\nあいう\nx\n")
+        );
+
+        let lines = DeterministicExpectedOutput::Lines {
+            text: "1\n2\n".into(),
+        };
+        assert_eq!(None, first_mismatch(&lines, "1\n2"));
+        assert_eq!(Some(position(2, 1)), first_mismatch(&lines, "1\n"));
+
+        let split_whitespace = DeterministicExpectedOutput::SplitWhitespace {
+            text: "1 2 3\n".into(),
+        };
+        assert_eq!(
+            Some(position(2, 3)),
+            first_mismatch(&split_whitespace, "1\n  4 3\n"),
+        );
+        assert_eq!(
+            Some(position(1, 4)),
+            first_mismatch(&split_whitespace, "1 2"),
+        );
+
+        let case_insensitive = DeterministicExpectedOutput::CaseInsensitive {
+            text: "Yes No\n".into(),
+        };
+        assert_eq!(
+            Some(position(1, 5)),
+            first_mismatch(&case_insensitive, "YES Yes\n"),
+        );
+    }
+
+    #[test]
+    fn float_errors() {
+        assert_eq!(None, float_error("1.5\n", "1.5\n"));
+
+        let note = float_error("100 0 2\n", "101 1e-9 2\n").unwrap();
+        assert_eq!(
+            "max absolute error: 1.000e0 at 1:1 (expected `100`, got `101`), \
+             max relative error: 1.000e-2 at 1:1 (expected `100`, got `101`)",
+            note.to_string(),
+        );
+        let (absolute, relative) = match note {
+            WrongAnswerNote::FloatError {
+                max_absolute_error,
+                max_relative_error,
+            } => (max_absolute_error, max_relative_error),
+            _ => unreachable!(),
+        };
+        let relative = relative.unwrap();
+        assert_eq!((1.0, position(1, 1)), (absolute.error, absolute.position));
+        // The infinite relative error against `0` is skipped.
+        assert_eq!((0.01, position(1, 1)), (relative.error, relative.position));
+        assert_eq!(("100", "101"), (&*relative.expected, &*relative.actual));
+
+        let note = float_error("0\n", "0.5\n").unwrap();
+        assert!(matches!(
+            &note,
+            WrongAnswerNote::FloatError {
+                max_absolute_error,
+                max_relative_error: None,
+            } if max_absolute_error.error == 0.5 && max_absolute_error.position == position(1, 1)
+        ));
+        assert_eq!(
+            "max absolute error: 5.000e-1 at 1:1 (expected `0`, got `0.5`)",
+            note.to_string(),
+        );
+    }
+
+    #[test]
+    fn notes() {
+        let expected = DeterministicExpectedOutput::Exact {
+            text: "Yes\nNo\n".into(),
+        };
+        assert_eq!(
+            vec![
+                WrongAnswerNote::FirstMismatch(position(1, 2)),
+                WrongAnswerNote::CaseMismatch,
+            ],
+            diagnose(&expected, "YES\nNO\n"),
+        );
+        assert_eq!(
+            vec![
+                WrongAnswerNote::FirstMismatch(position(2, 3)),
+                WrongAnswerNote::WordsMatched,
+                WrongAnswerNote::MissingTrailingNewline,
+            ],
+            diagnose(&expected, "Yes\nNo"),
+        );
+        assert_eq!(
+            vec![
+                WrongAnswerNote::FirstMismatch(position(2, 1)),
+                WrongAnswerNote::LineCount {
+                    expected: 2,
+                    actual: 1,
+                },
+                WrongAnswerNote::PrefixOfExpected,
+            ],
+            diagnose(&expected, "Yes\n"),
+        );
+    }
+}
@@ -0,0 +1,320 @@
+//! Runs solutions while watching their time and restricting their memory usage.
+
+use crate::ResourceUsage;
+use bikecleats_testsuite::ByteSize;
+use std::{
+    io,
+    process::{Command, ExitStatus},
+    time::{Duration, Instant},
+};
+
+#[cfg(unix)]
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Killed {
+    Timeout,
+}
+
+#[derive(Debug)]
+pub(crate) struct Finished {
+    pub(crate) status: ExitStatus,
+    pub(crate) usage: ResourceUsage,
+    pub(crate) killed: Option<Killed>,
+}
+
+/// A running process.
+///
+/// On Unix, the process is put in a new process group, and the whole group is killed when the
+/// process exits or is killed, so that no grandchildren are left behind. On other platforms,
+/// only the process itself is killed.
+///
+/// If this is dropped before the process exits, the process is killed.
+pub(crate) struct Process {
+    pub(crate) stdin: Option<tokio::fs::File>,
+    pub(crate) stdout: Option<tokio::fs::File>,
+    pub(crate) stderr: Option<tokio::fs::File>,
+    pub(crate) started: Instant,
+    guard: KillOnDrop,
+}
+
+impl Process {
+    pub(crate) fn spawn(cmd: &mut Command) -> io::Result<Self> {
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(cmd, 0);
+
+        let started = Instant::now();
+        let mut child = cmd.spawn()?;
+
+        Ok(Self {
+            stdin: child.stdin.take().map(pipe),
+            stdout: child.stdout.take().map(pipe),
+            stderr: child.stderr.take().map(pipe),
+            started,
+            guard: KillOnDrop::new(child),
+        })
+    }
+
+    /// Waits for the process.
+    ///
+    /// The process is killed when it runs longer than `timeout`.
+    #[cfg(unix)]
+    pub(crate) async fn wait(self, timeout: Option<Duration>) -> io::Result<Finished> {
+        let Self { started, guard, .. } = self;
+        let pid = guard.pid;
+
+        let mut wait = tokio::task::spawn_blocking({
+            let reaped = guard.reaped.clone();
+            move || wait4(pid, &reaped)
+        });
+
+        let mut killed = None;
+
+        let (status, rusage, finished) = loop {
+            if let Ok(result) = tokio::time::timeout(POLL_INTERVAL, &mut wait).await {
+                break result??;
+            }
+            if killed.is_none() && matches!(timeout, Some(t) if started.elapsed() > t) {
+                guard.kill();
+                killed = Some(Killed::Timeout);
+            }
+        };
+
+        // `ru_maxrss` is in bytes on macOS and in kilobytes elsewhere.
+        let max_rss = rusage.ru_maxrss as u64;
+        let max_rss = if cfg!(target_os = "macos") {
+            max_rss
+        } else {
+            max_rss * 1024
+        };
+
+        return Ok(Finished {
+            status,
+            usage: ResourceUsage {
+                wall: finished - started,
+                user: Some(duration(rusage.ru_utime)),
+                sys: Some(duration(rusage.ru_stime)),
+                max_rss: Some(ByteSize::from_bytes(max_rss)),
+            },
+            killed,
+        });
+
+        fn wait4(
+            pid: libc::pid_t,
+            reaped: &Mutex<bool>,
+        ) -> io::Result<(ExitStatus, libc::rusage, Instant)> {
+            use std::{mem, os::unix::process::ExitStatusExt as _};
+
+            // Waits without reaping so that `KillOnDrop::kill` never sends a signal to a
+            // reused PID.
+            loop {
+                let mut info = unsafe { mem::zeroed::<libc::siginfo_t>() };
+                let options = libc::WEXITED | libc::WNOWAIT;
+                if unsafe { libc::waitid(libc::P_PID, pid as _, &mut info, options) } == 0 {
+                    break;
+                }
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+
+            let finished = Instant::now();
+
+            let mut reaped = reaped.lock().unwrap();
+            // The process is a zombie at this point, so the group ID still belongs to it.
+            kill_group(pid);
+            let mut status = 0;
+            let mut rusage = unsafe { mem::zeroed::<libc::rusage>() };
+            loop {
+                if unsafe { libc::wait4(pid, &mut status, 0, &mut rusage) } == pid {
+                    break;
+                }
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+            *reaped = true;
+
+            Ok((ExitStatus::from_raw(status), rusage, finished))
+        }
+
+        fn duration(t: libc::timeval) -> Duration {
+            Duration::from_secs(t.tv_sec as _) + Duration::from_micros(t.tv_usec as _)
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) async fn wait(self, timeout: Option<Duration>) -> io::Result<Finished> {
+        let Self {
+            started, mut guard, ..
+        } = self;
+        let mut killed = None;
+
+        loop {
+            if let Some(status) = guard.0.try_wait()? {
+                return Ok(Finished {
+                    status,
+                    usage: ResourceUsage {
+                        wall: started.elapsed(),
+                        ..ResourceUsage::default()
+                    },
+                    killed,
+                });
+            }
+            if killed.is_none() && matches!(timeout, Some(t) if started.elapsed() > t) {
+                guard.0.kill()?;
+                killed = Some(Killed::Timeout);
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[cfg(unix)]
+struct KillOnDrop {
+    pid: libc::pid_t,
+    reaped: Arc<Mutex<bool>>,
+}
+
+#[cfg(unix)]
+impl KillOnDrop {
+    fn new(child: std::process::Child) -> Self {
+        Self {
+            pid: child.id() as _,
+            reaped: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    fn kill(&self) {
+        // Holding the lock, the PID cannot be reused by another process.
+        let reaped = self.reaped.lock().unwrap();
+        if !*reaped {
+            kill_group(self.pid);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+struct KillOnDrop(std::process::Child);
+
+#[cfg(not(unix))]
+impl KillOnDrop {
+    fn new(child: std::process::Child) -> Self {
+        Self(child)
+    }
+
+    fn kill(&mut self) {
+        let _ = self.0.kill();
+    }
+}
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+#[cfg(unix)]
+fn kill_group(pgid: libc::pid_t) {
+    unsafe {
+        libc::kill(-pgid, libc::SIGKILL);
+    }
+}
+
+#[cfg(unix)]
+fn pipe(pipe: impl Into<std::os::unix::io::OwnedFd>) -> tokio::fs::File {
+    tokio::fs::File::from_std(pipe.into().into())
+}
+
+#[cfg(windows)]
+fn pipe(pipe: impl Into<std::os::windows::io::OwnedHandle>) -> tokio::fs::File {
+    tokio::fs::File::from_std(pipe.into().into())
+}
+
+/// Returns whether this process is allowed to run on the CPU `cpu`.
+#[cfg(target_os = "linux")]
+pub(crate) fn is_cpu_available(cpu: usize) -> io::Result<bool> {
+    use std::mem;
+
+    if cpu >= libc::CPU_SETSIZE as usize {
+        return Ok(false);
+    }
+    unsafe {
+        let mut set = mem::zeroed::<libc::cpu_set_t>();
+        if libc::sched_getaffinity(0, mem::size_of::<libc::cpu_set_t>(), &mut set) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(libc::CPU_ISSET(cpu, &set))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn is_cpu_available(_: usize) -> io::Result<bool> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "pinning to a CPU is only supported on Linux",
+    ))
+}
+
+/// Makes the process run only on the CPU `cpu`, which should be checked with
+/// [`is_cpu_available`].
+#[cfg(target_os = "linux")]
+pub(crate) fn pin_to_cpu(cmd: &mut Command, cpu: usize) -> io::Result<()> {
+    use std::{mem, os::unix::process::CommandExt as _};
+
+    unsafe {
+        cmd.pre_exec(move || {
+            let mut set = mem::zeroed::<libc::cpu_set_t>();
+            libc::CPU_SET(cpu, &mut set);
+            if libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn pin_to_cpu(_: &mut Command, _: usize) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "pinning to a CPU is only supported on Linux",
+    ))
+}
+
+/// Restricts the data segment of the process and its descendants to `bytes` with `RLIMIT_DATA`.
+///
+/// `RLIMIT_DATA` is used instead of `RLIMIT_AS`, since runtimes such as the JVM or Go reserve far
+/// more address space than they use. Since Linux 4.7, it covers private writable mappings as well
+/// as the heap. An allocation beyond the limit fails inside the process.
+#[cfg(unix)]
+pub(crate) fn limit_memory(cmd: &mut Command, bytes: u64) -> io::Result<()> {
+    use std::os::unix::process::CommandExt as _;
+
+    unsafe {
+        cmd.pre_exec(move || {
+            let limit = libc::rlimit {
+                rlim_cur: bytes as _,
+                rlim_max: bytes as _,
+            };
+            if libc::setrlimit(libc::RLIMIT_DATA, &limit) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn limit_memory(_: &mut Command, _: u64) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "memory limits are only supported on Unix",
+    ))
+}
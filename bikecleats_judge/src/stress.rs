@@ -0,0 +1,368 @@
+//! Stress testing against a brute-force solution.
+
+use crate::{
+    judge_batch_test_case, open_file, run, BatchCheckers, Checkers, CommandExpression, CtrlC,
+    JudgeOutcome, Verdict,
+};
+use anyhow::{bail, Context as _};
+use bikecleats_testsuite::{
+    BatchTestCase, BatchTestSuite, PartialBatchTestCase, DEFAULT_GENERATOR_TIMELIMIT,
+};
+use futures_util::future;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::{
+    collections::HashSet,
+    future::Future,
+    iter,
+    num::NonZeroUsize,
+    ops::Range,
+    path::Path,
+    process::Stdio,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// Settings for [`stress`].
+#[derive(Debug, Clone)]
+pub struct StressSettings {
+    /// The seeds to try. Each seed is given to the generator and the reference as `$SEED`.
+    pub seeds: Range<u64>,
+    /// The number of seeds tried at once. Defaults to the number of CPUs.
+    pub jobs: Option<NonZeroUsize>,
+    /// The time limit for each run of the generator and the reference. Defaults to
+    /// [`DEFAULT_GENERATOR_TIMELIMIT`].
+    pub timelimit: Option<Duration>,
+    /// Checkers referred to by `Custom` matches.
+    pub checkers: Checkers,
+}
+
+/// An input on which the solution disagreed with the reference.
+#[derive(Debug, Clone)]
+pub struct Counterexample {
+    pub seed: u64,
+    /// The input and the output of the reference, named `stress-<seed>`.
+    pub test_case: PartialBatchTestCase,
+    /// The verdict on `test_case`.
+    pub outcome: JudgeOutcome,
+}
+
+/// Judges `cmd` on inputs printed by `generator`, against the outputs of `reference`.
+///
+/// The outputs are compared with the `match` and the limits of `suite`. Returns the
+/// counterexample with the smallest seed, or `None` if `cmd` passed all of the seeds.
+pub fn stress<C: 'static + Future<Output = tokio::io::Result<()>> + Send>(
+    draw_target: ProgressDrawTarget,
+    ctrl_c: fn() -> C,
+    generator: &CommandExpression,
+    reference: &CommandExpression,
+    cmd: &CommandExpression,
+    suite: &BatchTestSuite,
+    settings: &StressSettings,
+) -> anyhow::Result<Option<Counterexample>> {
+    let template = test_case(suite, partial_test_case(None, "".into(), Some("".into())))?;
+    let checkers = Arc::new(BatchCheckers::new(
+        &[template],
+        &settings.checkers,
+        &cmd.cwd,
+    )?);
+
+    let suite = Arc::new(BatchTestSuite {
+        cases: vec![],
+        extend: vec![],
+        ..suite.clone()
+    });
+    let (generator, reference, cmd) = (
+        Arc::new(generator.clone()),
+        Arc::new(reference.clone()),
+        Arc::new(cmd.clone()),
+    );
+
+    let tempdir = tempfile::Builder::new()
+        .prefix("bikecleats-stress-")
+        .tempdir()?;
+    let tempdir_path = Arc::new(tempdir.path().to_owned());
+
+    let pb = ProgressBar::new_spinner();
+    pb.set_draw_target(draw_target);
+    pb.set_style(ProgressStyle::default_spinner().template("{spinner} {msg:bold}"));
+    pb.set_message("Stress testing...");
+    pb.enable_steady_tick(50);
+
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_io()
+        .enable_time()
+        .build()?;
+
+    let jobs = settings.jobs.map_or_else(num_cpus::get, NonZeroUsize::get);
+    let timelimit = settings.timelimit.unwrap_or(DEFAULT_GENERATOR_TIMELIMIT);
+    let Range { start, end } = settings.seeds;
+
+    let found = rt.block_on({
+        let pb = pb.clone();
+        async move {
+            let ctrl_c = CtrlC::spawn(ctrl_c);
+
+            let next_seed = Arc::new(AtomicU64::new(start));
+            let found = Arc::new(Mutex::new(None::<Counterexample>));
+            let stop = Arc::new(AtomicBool::new(false));
+
+            let workers = (0..jobs)
+                .map(|i| {
+                    let (generator, reference, cmd, suite, checkers, tempdir_path) = (
+                        generator.clone(),
+                        reference.clone(),
+                        cmd.clone(),
+                        suite.clone(),
+                        checkers.clone(),
+                        tempdir_path.clone(),
+                    );
+                    let (next_seed, found, stop, pb) =
+                        (next_seed.clone(), found.clone(), stop.clone(), pb.clone());
+                    let ctrl_c = ctrl_c.clone();
+
+                    tokio::task::spawn(async move {
+                        let result = async {
+                            loop {
+                                let seed = next_seed.fetch_add(1, Ordering::SeqCst);
+                                if seed >= end
+                                    || stop.load(Ordering::SeqCst)
+                                    || matches!(&*found.lock().unwrap(), Some(c) if c.seed < seed)
+                                {
+                                    return Ok::<_, anyhow::Error>(());
+                                }
+
+                                tokio::task::block_in_place(|| {
+                                    pb.set_message(&format!("Stress testing... (seed {})", seed));
+                                });
+
+                                let input_path = tempdir_path.join(format!("{}-generated", i));
+                                let output_path = tempdir_path.join(format!("{}-reference", i));
+
+                                let mut generator = (*generator).clone();
+                                generator.env.insert("SEED".into(), seed.to_string().into());
+                                let input = run(
+                                    &generator,
+                                    Stdio::null(),
+                                    &input_path,
+                                    Some(timelimit),
+                                    &mut ctrl_c.subscribe()?,
+                                )
+                                .await
+                                .with_context(|| format!("The generator failed (seed {})", seed))?;
+
+                                let mut reference = (*reference).clone();
+                                reference.env.insert("SEED".into(), seed.to_string().into());
+                                let output = run(
+                                    &reference,
+                                    open_file(&input_path).await?.into(),
+                                    &output_path,
+                                    Some(timelimit),
+                                    &mut ctrl_c.subscribe()?,
+                                )
+                                .await
+                                .with_context(|| format!("The reference failed (seed {})", seed))?;
+
+                                let partial = partial_test_case(
+                                    Some(format!("stress-{}", seed)),
+                                    input,
+                                    Some(output),
+                                );
+
+                                let verdict = judge_batch_test_case(
+                                    i,
+                                    test_case(&suite, partial.clone())?,
+                                    &cmd,
+                                    None,
+                                    &checkers,
+                                    &tempdir_path,
+                                    ctrl_c.subscribe()?,
+                                )
+                                .await?;
+
+                                // The generator is to blame, not the solution.
+                                if let Verdict::InvalidInput { reason, .. } = &verdict {
+                                    bail!(
+                                        "The generator {:?} printed an invalid input (seed {}): {}",
+                                        iter::once(&generator.program)
+                                            .chain(&generator.args)
+                                            .collect::<Vec<_>>(),
+                                        seed,
+                                        reason,
+                                    );
+                                }
+
+                                if !matches!(verdict, Verdict::Accepted { .. }) {
+                                    let mut found = found.lock().unwrap();
+                                    if found.as_ref().map_or(true, |c| seed < c.seed) {
+                                        *found = Some(Counterexample {
+                                            seed,
+                                            test_case: partial,
+                                            outcome: JudgeOutcome {
+                                                verdicts: vec![verdict],
+                                            },
+                                        });
+                                    }
+                                }
+                            }
+                        }
+                        .await;
+                        if result.is_err() {
+                            // Lets the other workers stop at their next seed.
+                            stop.store(true, Ordering::SeqCst);
+                        }
+                        result
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            // Waits for all of the workers so that none of them is left writing to `tempdir`.
+            for result in future::join_all(workers).await {
+                result??;
+            }
+
+            let found = found.lock().unwrap().take();
+            Ok::<_, anyhow::Error>(found)
+        }
+    });
+
+    pb.finish_and_clear();
+    let closed = tempdir.close();
+    let found = found?;
+    closed?;
+    Ok(found)
+}
+
+fn partial_test_case(
+    name: Option<String>,
+    r#in: Arc<str>,
+    out: Option<Arc<str>>,
+) -> PartialBatchTestCase {
+    PartialBatchTestCase {
+        name,
+        r#in,
+        out,
+        alternatives: vec![],
+        timelimit: None,
+        memory_limit: None,
+        r#match: None,
+        presentation_error: None,
+        normalize: None,
+    }
+}
+
+/// Completes `case` with the settings of `suite`.
+fn test_case(suite: &BatchTestSuite, case: PartialBatchTestCase) -> anyhow::Result<BatchTestCase> {
+    let suite = BatchTestSuite {
+        cases: vec![case],
+        extend: vec![],
+        ..suite.clone()
+    };
+    let mut test_cases =
+        suite.load_test_cases(Path::new(""), None::<HashSet<String>>, |_| Ok(vec![]))?;
+    Ok(test_cases.remove(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{stress, Counterexample, StressSettings};
+    use crate::{CommandExpression, Verdict};
+    use bikecleats_testsuite::BatchTestSuite;
+    use futures_util::future::{self, Pending};
+    use indicatif::ProgressDrawTarget;
+    use std::{env, num::NonZeroUsize, ops::Range};
+
+    static SUITE: &str = r#"
+timelimit: 10s
+match: Lines
+cases: []
+"#;
+
+    fn never() -> Pending<tokio::io::Result<()>> {
+        future::pending()
+    }
+
+    fn sh(script: &str) -> anyhow::Result<CommandExpression> {
+        Ok(CommandExpression {
+            program: "sh".into(),
+            args: vec!["-c".into(), script.into()],
+            cwd: env::current_dir()?,
+            env: Default::default(),
+        })
+    }
+
+    /// Stress tests `solution` on `echo $SEED` for the seeds `0..10`, against `cat`.
+    fn stress_script(solution: &str, suite: &str) -> anyhow::Result<Option<Counterexample>> {
+        stress_scripts("echo $SEED", solution, suite, 0..10)
+    }
+
+    fn stress_scripts(
+        generator: &str,
+        solution: &str,
+        suite: &str,
+        seeds: Range<u64>,
+    ) -> anyhow::Result<Option<Counterexample>> {
+        let suite = serde_yaml::from_str::<BatchTestSuite>(suite)?;
+        let settings = StressSettings {
+            seeds,
+            jobs: NonZeroUsize::new(4),
+            timelimit: None,
+            checkers: Default::default(),
+        };
+        stress(
+            ProgressDrawTarget::hidden(),
+            never,
+            &sh(generator)?,
+            &sh("cat")?,
+            &sh(solution)?,
+            &suite,
+            &settings,
+        )
+    }
+
+    #[test]
+    fn smallest_counterexample() -> anyhow::Result<()> {
+        let found = stress_script("awk '{ print ($1 >= 3 && $1 != 5) ? 0 : $1 }'", SUITE)?.unwrap();
+        assert_eq!(3, found.seed);
+        assert_eq!(Some("stress-3"), found.test_case.name.as_deref());
+        assert_eq!("3\n", &*found.test_case.r#in);
+        assert_eq!(Some("3\n"), found.test_case.out.as_deref());
+        assert!(matches!(
+            found.outcome.verdicts[0],
+            Verdict::WrongAnswer { .. }
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn no_counterexample() -> anyhow::Result<()> {
+        assert!(stress_script("cat", SUITE)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_input() {
+        static SUITE: &str = r#"
+timelimit: 10s
+validator:
+  cmd: ["sh", "-c", "! grep -q 7"]
+match: Lines
+cases: []
+"#;
+
+        let err = stress_script("cat", SUITE).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("printed an invalid input (seed 7)"));
+    }
+
+    #[test]
+    fn stop_on_error() {
+        // Seeds after `1` are not tried, or this would not finish.
+        let err = stress_scripts("test $SEED -ne 1 && echo $SEED", "cat", SUITE, 0..u64::MAX)
+            .unwrap_err();
+        assert_eq!("The generator failed (seed 1)", err.to_string());
+    }
+}
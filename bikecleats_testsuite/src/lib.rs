@@ -1,20 +1,27 @@
-use anyhow::{bail, ensure, Context as _};
+use anyhow::{anyhow, bail, ensure, Context as _};
 use camino::Utf8PathBuf;
 use humantime_serde::Serde;
 use ignore::{overrides::OverrideBuilder, WalkBuilder};
 use itertools::{EitherOrBoth, Itertools as _};
 use maplit::hashmap;
-use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
+use regex::Regex;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest as _, Sha256};
 use std::{
-    borrow::Borrow,
+    borrow::{Borrow, Cow},
     collections::{BTreeMap, BTreeSet, HashMap, HashSet},
-    fs,
+    env, fmt, fs,
     hash::Hash,
-    path::Path,
+    io, iter, ops,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    slice,
     str::FromStr,
     sync::Arc,
-    time::Duration,
+    thread,
+    time::{Duration, Instant},
 };
+use unicode_normalization::UnicodeNormalization as _;
 use url::Url;
 
 #[derive(Deserialize, Serialize, Debug, PartialEq)]
@@ -33,6 +40,21 @@ impl TestSuite {
 
                 yaml += &key_value("type", "Batch").ok()?;
                 yaml += &key_value("timelimit", Serde::from(suite.timelimit)).ok()?;
+                if let Some(memory_limit) = suite.memory_limit {
+                    yaml += &key_value("memory_limit", memory_limit).ok()?;
+                }
+                if !suite.timelimit_mode.is_default() {
+                    yaml += &key_value("timelimit_mode", suite.timelimit_mode).ok()?;
+                }
+                if suite.presentation_error {
+                    yaml += &key_value("presentation_error", true).ok()?;
+                }
+                if !suite.normalize.is_default() {
+                    yaml += &key_value("normalize", suite.normalize).ok()?;
+                }
+                if let Some(validator) = &suite.validator {
+                    yaml += &key_value("validator", validator).ok()?;
+                }
                 yaml += &key_value("match", &suite.r#match).ok()?;
 
                 yaml += if suite.cases.is_empty() {
@@ -54,14 +76,31 @@ impl TestSuite {
                         part += &key_value_in_literal_style("out", out).ok()?;
                     }
 
+                    if !case.alternatives.is_empty() {
+                        let alternatives = case.alternatives.iter().map(|s| &**s).collect_vec();
+                        part += &key_value("alternatives", alternatives).ok()?;
+                    }
+
                     if let Some(timelimit) = case.timelimit {
                         part += &key_value("timelimit", Serde::from(timelimit)).ok()?;
                     }
 
+                    if let Some(memory_limit) = case.memory_limit {
+                        part += &key_value("memory_limit", memory_limit).ok()?;
+                    }
+
                     if let Some(r#match) = &case.r#match {
                         part += &key_value("match", r#match).ok()?;
                     }
 
+                    if let Some(presentation_error) = case.presentation_error {
+                        part += &key_value("presentation_error", presentation_error).ok()?;
+                    }
+
+                    if let Some(normalize) = case.normalize {
+                        part += &key_value("normalize", normalize).ok()?;
+                    }
+
                     for (i, line) in part.lines().enumerate() {
                         yaml += match i {
                             0 => "  - ",
@@ -149,11 +188,24 @@ impl TestSuite {
     }
 }
 
-#[derive(Deserialize, Serialize, Debug, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct BatchTestSuite {
     #[serde(default, with = "humantime_serde")]
     pub timelimit: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_limit: Option<ByteSize>,
+    #[serde(default, skip_serializing_if = "TimelimitMode::is_default")]
+    pub timelimit_mode: TimelimitMode,
     pub r#match: Match,
+    /// Whether to judge outputs whose whitespace-separated words match as Presentation Error
+    /// instead of Wrong Answer.
+    #[serde(default, skip_serializing_if = "ops::Not::not")]
+    pub presentation_error: bool,
+    #[serde(default, skip_serializing_if = "Normalize::is_default")]
+    pub normalize: Normalize,
+    /// Checks every input before the solution runs on it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validator: Option<Validator>,
     #[serde(default)]
     pub cases: Vec<PartialBatchTestCase>,
     #[serde(default)]
@@ -175,28 +227,80 @@ impl BatchTestSuite {
             cases.extend(extend.load_test_cases(parent_dir, &mut prepare_system_test_cases)?);
         }
 
-        let cases = cases
-            .into_iter()
-            .filter(
-                |PartialBatchTestCase { name, .. }| match (names.as_mut(), name.as_ref()) {
-                    (Some(names), Some(name)) => names.remove(name),
-                    _ => true,
-                },
-            )
-            .map(|case| BatchTestCase::new(case, self.timelimit, &self.r#match))
-            .collect();
+        let cases = filter_by_names(cases, &mut names, |case| case.name.as_deref())
+            .map(|case| BatchTestCase::new(case, self))
+            .collect::<anyhow::Result<_>>()?;
+
+        ensure_all_names_found(names)?;
+        Ok(cases)
+    }
+
+    /// Returns the names and the inputs of the test cases in `Text` that have no output files,
+    /// with the paths where their output files would be found.
+    pub fn text_cases_without_outputs(
+        &self,
+        parent_dir: &Path,
+    ) -> anyhow::Result<Vec<(String, Arc<str>, PathBuf)>> {
+        let mut ret = vec![];
 
-        if let Some(names) = names {
-            if !names.is_empty() {
-                bail!(
-                    "No such test cases: {:?}",
-                    names.iter().map(Borrow::borrow).collect::<BTreeSet<_>>(),
-                );
+        for extend in &self.extend {
+            if let Additional::Text {
+                path, r#in, out, ..
+            } = extend
+            {
+                let (base, cases) = text_files(parent_dir, path, r#in, out)?;
+
+                for (name, r#in, _) in cases.into_iter().filter(|(_, _, out)| out.is_none()) {
+                    // Only patterns like `/out/*.txt` are supported.
+                    let pattern = out.trim_start_matches('/');
+                    ensure!(
+                        pattern.matches('*').count() == 1
+                            && !pattern.contains(&['?', '[', '{', '!', '\\'][..])
+                            && !pattern.contains("**"),
+                        "Could not determine the output file from {:?}",
+                        out,
+                    );
+                    let out_path = base.join(pattern.replacen('*', &name, 1));
+                    ensure!(
+                        out_path.file_stem() == Some(name.as_ref()),
+                        "The name of {} would not be {:?}",
+                        out_path.display(),
+                        name,
+                    );
+                    ret.push((name, r#in, out_path));
+                }
             }
         }
 
-        Ok(cases)
+        Ok(ret)
+    }
+}
+
+fn filter_by_names<'a, T: 'a, S: Borrow<str> + Eq + Hash>(
+    cases: impl IntoIterator<Item = T> + 'a,
+    names: &'a mut Option<HashSet<S>>,
+    name: fn(&T) -> Option<&str>,
+) -> impl Iterator<Item = T> + 'a {
+    cases
+        .into_iter()
+        .filter(move |case| match (names.as_mut(), name(case)) {
+            (Some(names), Some(name)) => names.remove(name),
+            _ => true,
+        })
+}
+
+fn ensure_all_names_found<S: Borrow<str> + Eq + Hash>(
+    names: Option<HashSet<S>>,
+) -> anyhow::Result<()> {
+    if let Some(names) = names {
+        if !names.is_empty() {
+            bail!(
+                "No such test cases: {:?}",
+                names.iter().map(Borrow::borrow).collect::<BTreeSet<_>>(),
+            );
+        }
     }
+    Ok(())
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
@@ -206,12 +310,25 @@ pub struct PartialBatchTestCase {
     pub r#in: Arc<str>,
     #[serde(default, with = "serde_fn::option_arc_str")]
     pub out: Option<Arc<str>>,
+    /// Other outputs that are also accepted.
+    #[serde(
+        default,
+        with = "serde_fn::vec_arc_str",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub alternatives: Vec<Arc<str>>,
     #[serde(default, with = "humantime_serde")]
     pub timelimit: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_limit: Option<ByteSize>,
     pub r#match: Option<Match>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presentation_error: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalize: Option<Normalize>,
 }
 
-#[derive(Deserialize, Serialize, Debug, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 #[serde(tag = "type")]
 pub enum Additional {
     Text {
@@ -224,8 +341,62 @@ pub enum Additional {
             skip_serializing_if = "Option::is_none"
         )]
         timelimit: Option<Duration>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        memory_limit: Option<ByteSize>,
         #[serde(skip_serializing_if = "Option::is_none")]
         r#match: Option<Match>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        presentation_error: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        normalize: Option<Normalize>,
+    },
+    /// Test cases whose inputs are printed by `generator` for each seed.
+    ///
+    /// The seed is given as `$SEED`. The expected outputs are printed by `reference`, which
+    /// reads the input from its stdin. Both are cached in `cache`, keyed by the commands, the
+    /// files they refer to, and the seed.
+    Generated {
+        generator: CheckerCommand,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reference: Option<CheckerCommand>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        shell: Option<CheckerShell>,
+        seeds: Seeds,
+        /// The prefix of the names. Defaults to `generated`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        /// Defaults to `.cache/bikecleats`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache: Option<Utf8PathBuf>,
+        /// Files whose contents are part of the cache keys.
+        ///
+        /// The words of `generator` and `reference` that are paths of existing files are included
+        /// automatically, but files that are only referred to in other ways (e.g. `gen.py>out` or
+        /// `< params.txt`) or read by the commands themselves need to be listed here.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        depends: Vec<Utf8PathBuf>,
+        /// The time limit for each run of `generator` and `reference`. Defaults to
+        /// [`DEFAULT_GENERATOR_TIMELIMIT`].
+        #[serde(
+            default,
+            with = "humantime_serde",
+            skip_serializing_if = "Option::is_none"
+        )]
+        generator_timelimit: Option<Duration>,
+        #[serde(
+            default,
+            with = "humantime_serde",
+            skip_serializing_if = "Option::is_none"
+        )]
+        timelimit: Option<Duration>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        memory_limit: Option<ByteSize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        r#match: Option<Match>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        presentation_error: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        normalize: Option<Normalize>,
     },
     SystemTestCases {
         #[serde(skip_serializing_if = "Option::is_none")]
@@ -243,75 +414,118 @@ impl Additional {
     ) -> anyhow::Result<Vec<PartialBatchTestCase>> {
         match self {
             Self::Text {
-                path: base,
+                path,
                 r#in,
                 out,
                 r#match,
                 timelimit,
+                memory_limit,
+                presentation_error,
+                normalize,
             } => {
-                let base = Path::new(base);
-                let base = parent_dir.join(base.strip_prefix(".").unwrap_or(base));
-                let base = base.strip_prefix(".").unwrap_or(&base);
-
-                let mut cases = BTreeMap::<_, (Option<_>, Option<_>)>::new();
-
-                let walk = |overrides| -> _ {
-                    WalkBuilder::new(base)
-                        .max_depth(Some(128))
-                        .overrides(overrides)
-                        .standard_filters(false)
-                        .build()
-                        .map::<anyhow::Result<_>, _>(|entry| {
-                            let path = entry?.into_path();
-
-                            if path.is_dir() {
-                                return Ok(None);
-                            }
-
-                            let name = path
-                                .file_stem()
-                                .unwrap_or_default()
-                                .to_string_lossy()
-                                .into_owned();
-
-                            let content = fs::read_to_string(&path)
-                                .with_context(|| format!("Could not read {}", path.display()))?
-                                .into();
-
-                            Ok(Some((name, content)))
-                        })
-                        .flat_map(Result::transpose)
-                };
+                let (_, cases) = text_files(parent_dir, path, r#in, out)?;
 
-                for result in walk(OverrideBuilder::new(base).add(r#in)?.build()?) {
-                    let (name, content) = result?;
-                    let (entry, _) = cases.entry(name.clone()).or_default();
-                    ensure!(entry.is_none(), "Duplicated name: {:?}", name);
-                    *entry = Some(content);
+                Ok(cases
+                    .into_iter()
+                    .map(|(name, r#in, out)| PartialBatchTestCase {
+                        name: Some(name),
+                        r#in,
+                        out,
+                        alternatives: vec![],
+                        timelimit: *timelimit,
+                        memory_limit: *memory_limit,
+                        r#match: r#match.clone(),
+                        presentation_error: *presentation_error,
+                        normalize: *normalize,
+                    })
+                    .collect())
+            }
+            Self::Generated {
+                generator,
+                reference,
+                shell,
+                seeds,
+                name,
+                cache,
+                depends,
+                generator_timelimit,
+                timelimit,
+                memory_limit,
+                r#match,
+                presentation_error,
+                normalize,
+            } => {
+                for cmd in iter::once(generator).chain(reference) {
+                    ensure!(
+                        !matches!(cmd, CheckerCommand::Argv(argv) if argv.is_empty()),
+                        "Empty command in `Generated`",
+                    );
                 }
 
-                for result in walk(OverrideBuilder::new(base).add(out)?.build()?) {
-                    let (name, content) = result?;
-                    let (_, entry) = cases.entry(name.clone()).or_default();
-                    ensure!(entry.is_none(), "Duplicated name: {:?}", name);
-                    *entry = Some(content);
-                }
+                let generator_timelimit =
+                    generator_timelimit.unwrap_or(DEFAULT_GENERATOR_TIMELIMIT);
 
-                cases
-                    .into_iter()
-                    .map(|kv| {
-                        let (name, r#in, out) = match kv {
-                            (_, (None, None)) => unreachable!(),
-                            (name, (None, Some(_))) => bail!("No input file for {:?}", name),
-                            (name, (Some(r#in), out)) => (name, r#in, out),
-                        };
+                let cache = match cache {
+                    Some(cache) => parent_dir.join(cache),
+                    None => parent_dir.join(".cache").join("bikecleats"),
+                };
+                fs::create_dir_all(&cache)
+                    .with_context(|| format!("Could not create {}", cache.display()))?;
+
+                let generator_key = cache_key(generator, *shell, depends, parent_dir)?;
+                let reference_key = reference
+                    .as_ref()
+                    .map(|reference| cache_key(reference, *shell, depends, parent_dir))
+                    .transpose()?;
+
+                seeds
+                    .iter()
+                    .map(|seed| {
+                        let in_key = hex(Sha256::new()
+                            .chain(&generator_key)
+                            .chain(seed.to_le_bytes())
+                            .finalize());
+                        let in_path = cache.join(format!("{}.in", in_key));
+                        let r#in = run_cached(&in_path, generator_timelimit, || {
+                            let mut cmd = command(generator, *shell, parent_dir)?;
+                            cmd.env("SEED", seed.to_string()).stdin(Stdio::null());
+                            Ok(cmd)
+                        })?;
+
+                        let out = reference_key
+                            .as_ref()
+                            .zip(reference.as_ref())
+                            .map(|(reference_key, reference)| {
+                                let out_key = hex(Sha256::new()
+                                    .chain(reference_key)
+                                    .chain(&in_key)
+                                    .finalize());
+                                let out_path = cache.join(format!("{}.out", out_key));
+                                run_cached(&out_path, generator_timelimit, || {
+                                    let stdin = fs::File::open(&in_path).with_context(|| {
+                                        format!("Could not open {}", in_path.display())
+                                    })?;
+                                    let mut cmd = command(reference, *shell, parent_dir)?;
+                                    cmd.env("SEED", seed.to_string()).stdin(stdin);
+                                    Ok(cmd)
+                                })
+                            })
+                            .transpose()?;
 
                         Ok(PartialBatchTestCase {
-                            name: Some(name),
+                            name: Some(format!(
+                                "{}-{}",
+                                name.as_deref().unwrap_or("generated"),
+                                seed,
+                            )),
                             r#in,
                             out,
+                            alternatives: vec![],
                             timelimit: *timelimit,
+                            memory_limit: *memory_limit,
                             r#match: r#match.clone(),
+                            presentation_error: *presentation_error,
+                            normalize: *normalize,
                         })
                     })
                     .collect()
@@ -321,176 +535,1195 @@ impl Additional {
     }
 }
 
+/// Reads the files of `Additional::Text`.
+///
+/// Returns the base directory, and the names, the inputs, and the outputs of the test cases.
+#[allow(clippy::type_complexity)]
+fn text_files(
+    parent_dir: &Path,
+    path: &Utf8PathBuf,
+    r#in: &str,
+    out: &str,
+) -> anyhow::Result<(PathBuf, Vec<(String, Arc<str>, Option<Arc<str>>)>)> {
+    let base = Path::new(path);
+    let base = parent_dir.join(base.strip_prefix(".").unwrap_or(base));
+    let base = base.strip_prefix(".").unwrap_or(&base);
+
+    let mut cases = BTreeMap::<_, (Option<_>, Option<_>)>::new();
+
+    let walk = |overrides| -> _ {
+        WalkBuilder::new(base)
+            .max_depth(Some(128))
+            .overrides(overrides)
+            .standard_filters(false)
+            .build()
+            .map::<anyhow::Result<_>, _>(|entry| {
+                let path = entry?.into_path();
+
+                if path.is_dir() {
+                    return Ok(None);
+                }
+
+                let name = path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned();
+
+                let content = fs::read_to_string(&path)
+                    .with_context(|| format!("Could not read {}", path.display()))?
+                    .into();
+
+                Ok(Some((name, content)))
+            })
+            .flat_map(Result::transpose)
+    };
+
+    for result in walk(OverrideBuilder::new(base).add(r#in)?.build()?) {
+        let (name, content) = result?;
+        let (entry, _) = cases.entry(name.clone()).or_default();
+        ensure!(entry.is_none(), "Duplicated name: {:?}", name);
+        *entry = Some(content);
+    }
+
+    for result in walk(OverrideBuilder::new(base).add(out)?.build()?) {
+        let (name, content) = result?;
+        let (_, entry) = cases.entry(name.clone()).or_default();
+        ensure!(entry.is_none(), "Duplicated name: {:?}", name);
+        *entry = Some(content);
+    }
+
+    let cases = cases
+        .into_iter()
+        .map(|kv| match kv {
+            (_, (None, None)) => unreachable!(),
+            (name, (None, Some(_))) => bail!("No input file for {:?}", name),
+            (name, (Some(r#in), out)) => Ok((name, r#in, out)),
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok((base.to_owned(), cases))
+}
+
+/// Seeds given to a generator.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Seeds {
+    List(Vec<u64>),
+    /// `start..end`.
+    Range {
+        start: u64,
+        end: u64,
+    },
+}
+
+impl Seeds {
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        let (list, range) = match self {
+            Self::List(list) => (&**list, 0..0),
+            Self::Range { start, end } => (&[][..], *start..*end),
+        };
+        list.iter().copied().chain(range)
+    }
+}
+
+/// The default value of the time limit for generators and references of `Generated`.
+pub const DEFAULT_GENERATOR_TIMELIMIT: Duration = Duration::from_secs(10);
+
+/// Hashes `cmd`, the contents of the files it refers to, and the contents of `depends`.
+///
+/// Only the whitespace-separated words of `cmd` that are paths of existing files are taken as
+/// the files it refers to.
+fn cache_key(
+    cmd: &CheckerCommand,
+    shell: Option<CheckerShell>,
+    depends: &[Utf8PathBuf],
+    cwd: &Path,
+) -> anyhow::Result<Vec<u8>> {
+    let words = match cmd {
+        CheckerCommand::Script(script) => script.split_whitespace().collect(),
+        CheckerCommand::Argv(argv) => argv.iter().map(|s| &**s).collect::<Vec<_>>(),
+    };
+
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{:?}", (cmd, cmd.shell(shell))));
+    for depend in depends {
+        let path = cwd.join(depend);
+        let content =
+            fs::read(&path).with_context(|| format!("Could not read {}", path.display()))?;
+        hasher.update(depend.as_str());
+        hasher.update(Sha256::digest(&content));
+    }
+    for word in words {
+        let path = cwd.join(word);
+        if path.is_file() {
+            let content =
+                fs::read(&path).with_context(|| format!("Could not read {}", path.display()))?;
+            hasher.update(word);
+            hasher.update(Sha256::digest(&content));
+        }
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+fn hex(bytes: impl AsRef<[u8]>) -> String {
+    bytes
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Returns the content of `path`, creating it from the stdout of `cmd` if it does not exist.
+///
+/// `cmd` is killed if it runs longer than `timelimit`.
+fn run_cached(
+    path: &Path,
+    timelimit: Duration,
+    cmd: impl FnOnce() -> anyhow::Result<Command>,
+) -> anyhow::Result<Arc<str>> {
+    if path.exists() {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        return Ok(content.into());
+    }
+
+    let mut cmd = cmd()?;
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .with_context(|| format!("Could not execute {:?}", cmd))?;
+
+    let stdout = child.stdout.take().unwrap();
+    let stdout = thread::spawn(move || {
+        let mut stdout = stdout;
+        let mut buf = vec![];
+        io::Read::read_to_end(&mut stdout, &mut buf).map(|_| buf)
+    });
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if started.elapsed() > timelimit {
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!("{:?} timed out ({} ms)", cmd, timelimit.as_millis());
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    let stdout = stdout.join().unwrap()?;
+
+    ensure!(status.success(), "{:?} failed with {}", cmd, status);
+    let content =
+        String::from_utf8(stdout).with_context(|| format!("{:?} printed a non UTF-8 text", cmd))?;
+
+    let tmp = PathBuf::from(format!("{}.tmp", path.display()));
+    fs::write(&tmp, &content).with_context(|| format!("Could not write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("Could not write {}", path.display()))?;
+    Ok(content.into())
+}
+
+fn command(
+    cmd: &CheckerCommand,
+    shell: Option<CheckerShell>,
+    cwd: &Path,
+) -> anyhow::Result<Command> {
+    let mut ret = match cmd {
+        CheckerCommand::Script(script) => {
+            let shell = cmd.shell(shell).expect("should be `Some` for scripts");
+            let mut ret = Command::new(shell.find(cwd)?);
+            ret.arg("-c").arg(script);
+            ret
+        }
+        CheckerCommand::Argv(argv) => {
+            ensure!(
+                shell.is_none(),
+                "`shell` cannot be specified for a command in the list form",
+            );
+            let (program, args) = argv.split_first().with_context(|| "empty command")?;
+            let program = which::which_in(program, env::var_os("PATH"), cwd)
+                .map_err(|_| anyhow!("`{}` not found", program))?;
+            let mut ret = Command::new(program);
+            ret.args(args);
+            ret
+        }
+    };
+    ret.current_dir(cwd);
+    Ok(ret)
+}
+
+/// Which time the time limit is applied to.
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimelimitMode {
+    /// The elapsed real time.
+    #[default]
+    WallTime,
+    /// The sum of the user and system CPU time. Falls back to the wall time on platforms where
+    /// the CPU time cannot be measured.
+    CpuTime,
+}
+
+impl TimelimitMode {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub enum Match {
     Exact,
     SplitWhitespace,
     Lines,
+    /// Compares whitespace-separated words ignoring letter case.
+    CaseInsensitive,
+    /// Compares lines in any order.
+    UnorderedLines,
+    /// Compares whitespace-separated words in any order.
+    UnorderedWords,
+    /// Treats each line of the expected output as a regular expression that must match the
+    /// whole corresponding line.
+    ///
+    /// The patterns are compiled when the test cases are loaded, and are not modified by
+    /// `normalize`.
+    Regex,
     Float {
         relative_error: Option<PositiveFinite<f64>>,
         absolute_error: Option<PositiveFinite<f64>>,
+        #[serde(default, skip_serializing_if = "ToleranceMode::is_default")]
+        tolerance_mode: ToleranceMode,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min_decimal_places: Option<usize>,
+    },
+    /// Uses a checker registered with the judge as `name`.
+    Custom {
+        name: String,
+    },
+    /// Compares whitespace-separated words line by line, with a rule for each position.
+    Tokens {
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        rules: Vec<TokenRule>,
+        /// The rule for words that no rule in `rules` applies to.
+        #[serde(default, skip_serializing_if = "TokenMatch::is_default")]
+        default: TokenMatch,
     },
     Checker {
-        cmd: String,
-        shell: CheckerShell,
+        cmd: CheckerCommand,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        shell: Option<CheckerShell>,
+        #[serde(default, skip_serializing_if = "CheckerProtocol::is_default")]
+        protocol: CheckerProtocol,
+        #[serde(
+            default,
+            with = "humantime_serde",
+            skip_serializing_if = "Option::is_none"
+        )]
+        timelimit: Option<Duration>,
     },
 }
 
+/// Modifications applied to both expected and actual outputs before they are compared.
+///
+/// For `Checker`, the files given to the checker are normalized. The patterns of `Regex` are
+/// not normalized.
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct Normalize {
+    /// Replaces CRLF with LF.
+    #[serde(skip_serializing_if = "ops::Not::not")]
+    pub crlf: bool,
+    /// Removes whitespace at the end of each line.
+    #[serde(skip_serializing_if = "ops::Not::not")]
+    pub trailing_whitespace: bool,
+    /// Removes blank lines at the end.
+    #[serde(skip_serializing_if = "ops::Not::not")]
+    pub trailing_blank_lines: bool,
+    /// Applies Unicode Normalization Form C.
+    #[serde(skip_serializing_if = "ops::Not::not")]
+    pub nfc: bool,
+}
+
+impl Normalize {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.is_default() {
+            return text.into();
+        }
+
+        let mut text = text.to_owned();
+
+        if self.crlf {
+            text = text.replace("\r\n", "\n");
+        }
+
+        if self.trailing_whitespace {
+            text = text
+                .split_inclusive('\n')
+                .map(|line| {
+                    let newline = if line.ends_with('\n') { "\n" } else { "" };
+                    line.trim_end().to_owned() + newline
+                })
+                .collect();
+        }
+
+        if self.trailing_blank_lines {
+            let end = match text.trim_end().len() {
+                0 => 0,
+                end => text[end..].find('\n').map_or(text.len(), |i| end + i + 1),
+            };
+            text.truncate(end);
+        }
+
+        if self.nfc {
+            text = text.nfc().collect();
+        }
+
+        text.into()
+    }
+}
+
+/// A rule of `Tokens` for words at some positions.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct TokenRule {
+    /// The 1-based line number. Applies to every line if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    /// The 1-based index of the word in a line. Applies to every word if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    pub r#match: TokenMatch,
+}
+
+impl TokenRule {
+    fn applies_to(&self, line: usize, column: usize) -> bool {
+        self.line.map_or(true, |l| l == line) && self.column.map_or(true, |c| c == column)
+    }
+}
+
+/// How a word is compared.
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq)]
+pub enum TokenMatch {
+    #[default]
+    Exact,
+    CaseInsensitive,
+    Float {
+        relative_error: Option<PositiveFinite<f64>>,
+        absolute_error: Option<PositiveFinite<f64>>,
+        #[serde(default, skip_serializing_if = "ToleranceMode::is_default")]
+        tolerance_mode: ToleranceMode,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min_decimal_places: Option<usize>,
+    },
+}
+
+impl TokenMatch {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns whether `actual` is accepted as the word `expected`.
+    ///
+    /// For `Float`, numbers are compared as follows:
+    ///
+    /// - If `expected` is NaN, `actual` must be NaN.
+    /// - If `expected` is infinite, `actual` must be the same infinity.
+    /// - Otherwise, `actual` must be finite and within the errors. The relative error is
+    ///   computed against `expected`.
+    /// - If `min_decimal_places` is given and `expected` has a decimal point, `actual` must have
+    ///   at least that many digits after a decimal point.
+    ///
+    /// Words that are not numbers are compared exactly.
+    pub fn accepts(&self, expected: &str, actual: &str) -> bool {
+        match *self {
+            Self::Exact => expected == actual,
+            Self::CaseInsensitive => expected.to_lowercase() == actual.to_lowercase(),
+            Self::Float {
+                relative_error,
+                absolute_error,
+                tolerance_mode,
+                min_decimal_places,
+            } => {
+                let (v1, v2) = match (expected.parse::<f64>(), actual.parse::<f64>()) {
+                    (Ok(v1), Ok(v2)) => (v1, v2),
+                    _ => return expected == actual,
+                };
+
+                if let Some(min_decimal_places) = min_decimal_places {
+                    let decimal_places = |s: &str| {
+                        s.find('.')
+                            .map(|i| s[i + 1..].chars().take_while(char::is_ascii_digit).count())
+                    };
+                    if decimal_places(expected).is_some()
+                        && decimal_places(actual).unwrap_or(0) < min_decimal_places
+                    {
+                        return false;
+                    }
+                }
+
+                if v1.is_nan() {
+                    return v2.is_nan();
+                }
+                if v1.is_infinite() {
+                    return v1 == v2;
+                }
+                if !v2.is_finite() {
+                    return false;
+                }
+
+                let (absolute, relative) = DeterministicExpectedOutput::float_errors(v1, v2);
+                let within = |tolerance: Option<PositiveFinite<f64>>, error: f64, default| {
+                    tolerance.map_or(default, |t| error <= t.get())
+                };
+                match tolerance_mode {
+                    ToleranceMode::Either => {
+                        absolute == 0.0
+                            || within(absolute_error, absolute, false)
+                            || within(relative_error, relative, false)
+                    }
+                    ToleranceMode::Both => {
+                        // The relative error against `0` is not meaningful, so only the absolute
+                        // error is checked if it is given.
+                        let relative_ok = (v1 == 0.0 && absolute_error.is_some())
+                            || within(relative_error, relative, true);
+                        within(absolute_error, absolute, true) && relative_ok
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// How `relative_error` and `absolute_error` of `Float` are combined.
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToleranceMode {
+    /// Accepts a number within either of the errors. An error that is not given is treated as
+    /// `0`.
+    #[default]
+    Either,
+    /// Accepts a number within both of the errors. An error that is not given is not checked.
+    /// If the expected value is `0`, the relative error is not checked when the absolute error is
+    /// given.
+    Both,
+}
+
+impl ToleranceMode {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A command line of a checker or an interactor.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum CheckerCommand {
+    /// A script run with `shell` (`Bash` by default).
+    Script(String),
+    /// A program and its arguments, run directly without any shell.
+    Argv(Vec<String>),
+}
+
+impl From<&'_ str> for CheckerCommand {
+    fn from(script: &'_ str) -> Self {
+        Self::Script(script.to_owned())
+    }
+}
+
+impl CheckerCommand {
+    /// Returns the shell to run this command with.
+    pub fn shell(&self, shell: Option<CheckerShell>) -> Option<CheckerShell> {
+        match self {
+            Self::Script(_) => Some(shell.unwrap_or(CheckerShell::Bash)),
+            Self::Argv(_) => None,
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord)]
 pub enum CheckerShell {
     Bash,
+    Sh,
+}
+
+impl CheckerShell {
+    /// Finds the executable of this shell. On Windows, the one of Git for Windows is preferred.
+    pub fn find(self, cwd: &Path) -> anyhow::Result<PathBuf> {
+        static GIT_BASH: &str = r"C:\Program Files\Git\bin\bash.exe";
+        static GIT_SH: &str = r"C:\Program Files\Git\bin\sh.exe";
+
+        let (exe, git_exe) = match self {
+            Self::Bash => ("bash", GIT_BASH),
+            Self::Sh => ("sh", GIT_SH),
+        };
+        let exe = if cfg!(windows) && Path::new(git_exe).exists() {
+            git_exe
+        } else {
+            exe
+        };
+        which::which_in(exe, env::var_os("PATH"), cwd).map_err(|_| anyhow!("`{}` not found", exe))
+    }
+}
+
+/// How a checker receives the files and reports the result.
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord)]
+pub enum CheckerProtocol {
+    /// The paths are given as `$INPUT`, `$ACTUAL_OUTPUT`, and `$EXPECTED_OUTPUT`. Any non-zero
+    /// exit code is treated as Wrong Answer.
+    #[default]
+    Env,
+    /// The paths are appended to `cmd` as `input output answer`, and the exit code is
+    /// interpreted in the same way as [testlib](https://github.com/MikeMirzayanov/testlib)
+    /// (`0`: OK, `1`: WA, `2`: PE, `3`: FAIL, `7`: points).
+    ///
+    /// Points (`_points`) are treated as Accepted. The score is not parsed, but is kept in the
+    /// checker output, which is shown with the verdict.
+    Testlib,
+}
+
+impl CheckerProtocol {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A program that checks that an input satisfies the constraints of the problem.
+///
+/// The input is given to its stdin, and its path is also in `$INPUT`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Validator {
+    pub cmd: CheckerCommand,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<CheckerShell>,
+    #[serde(default, skip_serializing_if = "ValidatorProtocol::is_default")]
+    pub protocol: ValidatorProtocol,
+    #[serde(
+        default,
+        with = "humantime_serde",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timelimit: Option<Duration>,
+}
+
+/// How a validator reports the result.
+///
+/// A validator that is killed by a signal or times out is reported as Checker Failed, not as
+/// Invalid Input.
+#[derive(Deserialize, Serialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidatorProtocol {
+    /// Any non-zero exit code means the input is invalid.
+    #[default]
+    ExitCode,
+    /// The exit code is interpreted in the same way as
+    /// [testlib](https://github.com/MikeMirzayanov/testlib) (`0`: valid, `3`: invalid). Other
+    /// exit codes mean the validator itself failed.
+    Testlib,
+}
+
+impl ValidatorProtocol {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, PartialEq)]
 pub struct InteractiveTestSuite {
     #[serde(default, with = "humantime_serde")]
     pub timelimit: Option<Duration>,
+    /// The memory limit for the solution. The interactor is not limited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_limit: Option<ByteSize>,
+    #[serde(default, skip_serializing_if = "TimelimitMode::is_default")]
+    pub timelimit_mode: TimelimitMode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interactor: Option<Interactor>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cases: Vec<PartialInteractiveTestCase>,
+}
+
+impl InteractiveTestSuite {
+    pub fn load_test_cases<S: Borrow<str> + Eq + Hash>(
+        &self,
+        mut names: Option<HashSet<S>>,
+    ) -> anyhow::Result<Vec<InteractiveTestCase>> {
+        let interactor = self
+            .interactor
+            .as_ref()
+            .with_context(|| "`interactor` is not set")?;
+
+        let cases = filter_by_names(self.cases.clone(), &mut names, |case| case.name.as_deref())
+            .map(|case| InteractiveTestCase {
+                name: case.name,
+                timelimit: case.timelimit.or(self.timelimit),
+                memory_limit: case.memory_limit.or(self.memory_limit),
+                timelimit_mode: self.timelimit_mode,
+                input: case.r#in,
+                interactor: interactor.clone(),
+            })
+            .collect();
+
+        ensure_all_names_found(names)?;
+        Ok(cases)
+    }
+}
+
+/// A program that talks with the solution through its stdin/stdout.
+///
+/// The input of each test case is given as a file whose path is in `$INPUT`. The exit code is
+/// interpreted according to `protocol`. With `Testlib`, the paths of the input and an output file
+/// are appended to `cmd` as `input output`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Interactor {
+    pub cmd: CheckerCommand,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<CheckerShell>,
+    #[serde(default, skip_serializing_if = "CheckerProtocol::is_default")]
+    pub protocol: CheckerProtocol,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct PartialInteractiveTestCase {
+    pub name: Option<String>,
+    #[serde(with = "serde_fn::arc_str")]
+    pub r#in: Arc<str>,
+    #[serde(default, with = "humantime_serde")]
+    pub timelimit: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_limit: Option<ByteSize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InteractiveTestCase {
+    pub name: Option<String>,
+    pub timelimit: Option<Duration>,
+    pub memory_limit: Option<ByteSize>,
+    pub timelimit_mode: TimelimitMode,
+    pub input: Arc<str>,
+    pub interactor: Interactor,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct BatchTestCase {
     pub name: Option<String>,
     pub timelimit: Option<Duration>,
+    pub memory_limit: Option<ByteSize>,
+    pub timelimit_mode: TimelimitMode,
+    pub presentation_error: bool,
+    pub normalize: Normalize,
+    pub validator: Option<Validator>,
     pub input: Arc<str>,
     pub output: ExpectedOutput,
 }
 
 impl BatchTestCase {
-    fn new(case: PartialBatchTestCase, timelimit: Option<Duration>, matching: &Match) -> Self {
-        BatchTestCase {
+    fn new(case: PartialBatchTestCase, suite: &BatchTestSuite) -> anyhow::Result<Self> {
+        let name = &case.name;
+        let output = ExpectedOutput::new(
+            case.out,
+            case.alternatives,
+            case.r#match.unwrap_or_else(|| suite.r#match.clone()),
+        )
+        .with_context(|| format!("Could not load {:?}", name.as_deref().unwrap_or("")))?;
+        Ok(BatchTestCase {
             name: case.name,
-            timelimit: case.timelimit.or(timelimit),
+            timelimit: case.timelimit.or(suite.timelimit),
+            memory_limit: case.memory_limit.or(suite.memory_limit),
+            timelimit_mode: suite.timelimit_mode,
+            presentation_error: case.presentation_error.unwrap_or(suite.presentation_error),
+            normalize: case.normalize.unwrap_or(suite.normalize),
+            validator: suite.validator.clone(),
             input: case.r#in,
-            output: ExpectedOutput::new(case.out, case.r#match.unwrap_or_else(|| matching.clone())),
-        }
+            output,
+        })
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub enum ExpectedOutput {
     Deterministic(DeterministicExpectedOutput),
+    /// Accepts an output that any of the candidates accepts.
+    AnyOf(Vec<DeterministicExpectedOutput>),
+    Custom {
+        #[serde(default, with = "serde_fn::option_arc_str")]
+        text: Option<Arc<str>>,
+        name: String,
+    },
     Checker {
+        #[serde(default, with = "serde_fn::option_arc_str")]
         text: Option<Arc<str>>,
-        cmd: String,
-        shell: CheckerShell,
+        cmd: CheckerCommand,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        shell: Option<CheckerShell>,
+        #[serde(default, skip_serializing_if = "CheckerProtocol::is_default")]
+        protocol: CheckerProtocol,
+        #[serde(
+            default,
+            with = "humantime_serde",
+            skip_serializing_if = "Option::is_none"
+        )]
+        timelimit: Option<Duration>,
     },
 }
 
 impl ExpectedOutput {
-    fn new(text: Option<Arc<str>>, matching: Match) -> Self {
-        match (text, matching) {
-            (text, Match::Checker { cmd, shell }) => Self::Checker { text, cmd, shell },
-            (Some(text), Match::Exact) => {
-                Self::Deterministic(DeterministicExpectedOutput::Exact { text })
-            }
-            (Some(text), Match::SplitWhitespace) => {
-                Self::Deterministic(DeterministicExpectedOutput::SplitWhitespace { text })
-            }
-            (Some(text), Match::Lines) => {
-                Self::Deterministic(DeterministicExpectedOutput::Lines { text })
-            }
-            (
-                Some(text),
-                Match::Float {
-                    relative_error,
-                    absolute_error,
-                },
-            ) => Self::Deterministic(DeterministicExpectedOutput::Float {
+    fn new(
+        text: Option<Arc<str>>,
+        alternatives: Vec<Arc<str>>,
+        matching: Match,
+    ) -> anyhow::Result<Self> {
+        if let Match::Checker {
+            cmd,
+            shell,
+            protocol,
+            timelimit,
+        } = matching
+        {
+            return Ok(Self::Checker {
                 text,
-                relative_error,
-                absolute_error,
-            }),
-            (None, _) => Self::Deterministic(DeterministicExpectedOutput::Pass),
+                cmd,
+                shell,
+                protocol,
+                timelimit,
+            });
         }
+        if let Match::Custom { name } = matching {
+            return Ok(Self::Custom { text, name });
+        }
+
+        let mut candidates = text
+            .into_iter()
+            .chain(alternatives)
+            .map(|text| DeterministicExpectedOutput::new(text, matching.clone()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(match candidates.len() {
+            0 => Self::Deterministic(DeterministicExpectedOutput::Pass),
+            1 => Self::Deterministic(candidates.pop().unwrap()),
+            _ => Self::AnyOf(candidates),
+        })
     }
 
     pub fn is_float(&self) -> bool {
         matches!(
-            self,
-            Self::Deterministic(DeterministicExpectedOutput::Float { .. })
+            self.candidates().first(),
+            Some(DeterministicExpectedOutput::Float { .. })
         )
     }
 
+    /// Returns the expected output, or the first candidate for `AnyOf`.
     pub fn expected_stdout(&self) -> Option<&str> {
+        self.candidates()
+            .first()
+            .and_then(DeterministicExpectedOutput::expected_stdout)
+    }
+
+    /// Returns the deterministic expected outputs. Empty for `Checker` and `Custom`.
+    pub fn candidates(&self) -> &[DeterministicExpectedOutput] {
         match self {
-            Self::Deterministic(expected) => expected.expected_stdout(),
-            Self::Checker { .. } => None,
+            Self::Deterministic(expected) => slice::from_ref(expected),
+            Self::AnyOf(candidates) => candidates,
+            Self::Custom { .. } | Self::Checker { .. } => &[],
         }
     }
 
     pub fn example(&self) -> Option<&str> {
         match self {
-            Self::Checker { text, .. } => text.as_deref(),
+            Self::Checker { text, .. } | Self::Custom { text, .. } => text.as_deref(),
             _ => None,
         }
     }
+
+    /// Replaces the expected output, keeping how outputs are compared. `AnyOf` becomes its
+    /// first candidate.
+    ///
+    /// Fails if `text` is not valid for `Regex`.
+    pub fn with_expected_stdout(&self, text: Arc<str>) -> anyhow::Result<Self> {
+        let mut ret = match self {
+            Self::AnyOf(candidates) => Self::Deterministic(candidates[0].clone()),
+            _ => self.clone(),
+        };
+        match &mut ret {
+            Self::Deterministic(DeterministicExpectedOutput::Regex { text: expected }) => {
+                *expected = RegexLines::new(text)?;
+            }
+            Self::Deterministic(expected) => {
+                if let Some(expected) = expected.text_mut() {
+                    *expected = text;
+                }
+            }
+            Self::Checker { text: expected, .. } | Self::Custom { text: expected, .. } => {
+                *expected = Some(text);
+            }
+            Self::AnyOf(_) => unreachable!(),
+        }
+        Ok(ret)
+    }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub enum DeterministicExpectedOutput {
     Pass,
     Exact {
+        #[serde(with = "serde_fn::arc_str")]
         text: Arc<str>,
     },
     SplitWhitespace {
+        #[serde(with = "serde_fn::arc_str")]
         text: Arc<str>,
     },
     Lines {
+        #[serde(with = "serde_fn::arc_str")]
+        text: Arc<str>,
+    },
+    CaseInsensitive {
+        #[serde(with = "serde_fn::arc_str")]
+        text: Arc<str>,
+    },
+    UnorderedLines {
+        #[serde(with = "serde_fn::arc_str")]
+        text: Arc<str>,
+    },
+    UnorderedWords {
+        #[serde(with = "serde_fn::arc_str")]
         text: Arc<str>,
     },
+    Regex {
+        text: RegexLines,
+    },
     Float {
+        #[serde(with = "serde_fn::arc_str")]
         text: Arc<str>,
         relative_error: Option<PositiveFinite<f64>>,
         absolute_error: Option<PositiveFinite<f64>>,
+        #[serde(default, skip_serializing_if = "ToleranceMode::is_default")]
+        tolerance_mode: ToleranceMode,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        min_decimal_places: Option<usize>,
+    },
+    Tokens {
+        #[serde(with = "serde_fn::arc_str")]
+        text: Arc<str>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        rules: Vec<TokenRule>,
+        #[serde(default, skip_serializing_if = "TokenMatch::is_default")]
+        default: TokenMatch,
     },
 }
 
 impl DeterministicExpectedOutput {
+    /// Returns `self` with the expected output normalized.
+    pub fn normalized(&self, normalize: Normalize) -> Self {
+        let mut this = self.clone();
+        if let Some(text) = this.text_mut() {
+            *text = normalize.apply(text).into();
+        }
+        this
+    }
+
+    /// Returns the expected output, unless it is compiled as `Regex`.
+    fn text_mut(&mut self) -> Option<&mut Arc<str>> {
+        match self {
+            Self::Pass | Self::Regex { .. } => None,
+            Self::Exact { text }
+            | Self::SplitWhitespace { text }
+            | Self::Lines { text }
+            | Self::CaseInsensitive { text }
+            | Self::UnorderedLines { text }
+            | Self::UnorderedWords { text }
+            | Self::Float { text, .. }
+            | Self::Tokens { text, .. } => Some(text),
+        }
+    }
+
+    fn new(text: Arc<str>, matching: Match) -> anyhow::Result<Self> {
+        Ok(match matching {
+            Match::Exact => Self::Exact { text },
+            Match::SplitWhitespace => Self::SplitWhitespace { text },
+            Match::Lines => Self::Lines { text },
+            Match::CaseInsensitive => Self::CaseInsensitive { text },
+            Match::UnorderedLines => Self::UnorderedLines { text },
+            Match::UnorderedWords => Self::UnorderedWords { text },
+            Match::Regex => Self::Regex {
+                text: RegexLines::new(text)?,
+            },
+            Match::Float {
+                relative_error,
+                absolute_error,
+                tolerance_mode,
+                min_decimal_places,
+            } => Self::Float {
+                text,
+                relative_error,
+                absolute_error,
+                tolerance_mode,
+                min_decimal_places,
+            },
+            Match::Tokens { rules, default } => Self::Tokens {
+                text,
+                rules,
+                default,
+            },
+            Match::Checker { .. } | Match::Custom { .. } => {
+                unreachable!("handled in `ExpectedOutput::new`")
+            }
+        })
+    }
+
     pub fn accepts(&self, actual: &str) -> bool {
         match self {
             Self::Pass => true,
             Self::Exact { text } => &**text == actual,
             Self::SplitWhitespace { text } => text.split_whitespace().eq(actual.split_whitespace()),
             Self::Lines { text } => text.lines().eq(actual.lines()),
+            Self::CaseInsensitive { text } => text
+                .split_whitespace()
+                .map(str::to_lowercase)
+                .eq(actual.split_whitespace().map(str::to_lowercase)),
+            Self::UnorderedLines { text } => text.lines().sorted().eq(actual.lines().sorted()),
+            Self::UnorderedWords { text } => text
+                .split_whitespace()
+                .sorted()
+                .eq(actual.split_whitespace().sorted()),
+            Self::Regex { text } => text.is_match(actual),
+            Self::Float { text, .. } | Self::Tokens { text, .. } => text
+                .lines()
+                .zip_longest(actual.lines())
+                .enumerate()
+                .all(|(i, zip)| {
+                    if let EitherOrBoth::Both(line1, line2) = zip {
+                        let (words1, words2) = (line1.split_whitespace(), line2.split_whitespace());
+                        words1
+                            .zip_longest(words2)
+                            .enumerate()
+                            .all(|(j, zip)| match zip {
+                                EitherOrBoth::Both(s1, s2) => {
+                                    self.accepts_word_at(i + 1, j + 1, s1, s2)
+                                }
+                                EitherOrBoth::Left(_) | EitherOrBoth::Right(_) => false,
+                            })
+                    } else {
+                        false
+                    }
+                }),
+        }
+    }
+
+    /// Returns whether `actual` is accepted as the word `expected`.
+    ///
+    /// See [`TokenMatch::accepts`]. For `Tokens`, `default` is used.
+    pub fn accepts_word(&self, expected: &str, actual: &str) -> bool {
+        self.token_match(None).accepts(expected, actual)
+    }
+
+    /// Returns whether `actual` is accepted as the word `expected` at the `column`-th word of
+    /// the `line`-th line. Both are 1-based.
+    pub fn accepts_word_at(
+        &self,
+        line: usize,
+        column: usize,
+        expected: &str,
+        actual: &str,
+    ) -> bool {
+        self.token_match(Some((line, column)))
+            .accepts(expected, actual)
+    }
+
+    fn token_match(&self, position: Option<(usize, usize)>) -> TokenMatch {
+        match self {
+            Self::CaseInsensitive { .. } => TokenMatch::CaseInsensitive,
             Self::Float {
-                text,
                 relative_error,
                 absolute_error,
-            } => {
-                let (text, actual) = (text.lines(), actual.lines());
-                let relative_error = relative_error.map(PositiveFinite::get).unwrap_or(0.0);
-                let absolute_error = absolute_error.map(PositiveFinite::get).unwrap_or(0.0);
+                tolerance_mode,
+                min_decimal_places,
+                ..
+            } => TokenMatch::Float {
+                relative_error: *relative_error,
+                absolute_error: *absolute_error,
+                tolerance_mode: *tolerance_mode,
+                min_decimal_places: *min_decimal_places,
+            },
+            Self::Tokens { rules, default, .. } => position
+                .and_then(|(line, column)| rules.iter().find(|rule| rule.applies_to(line, column)))
+                .map_or(*default, |rule| rule.r#match),
+            _ => TokenMatch::Exact,
+        }
+    }
+
+    /// Returns the absolute error and the relative error of `actual` against `expected`.
+    ///
+    /// The relative error is `0` if the values are equal, and infinite if `expected` is `0` and
+    /// `actual` is not.
+    pub fn float_errors(expected: f64, actual: f64) -> (f64, f64) {
+        let absolute = (expected - actual).abs();
+        let relative = if absolute == 0.0 {
+            0.0
+        } else {
+            absolute / expected.abs()
+        };
+        (absolute, relative)
+    }
+
+    pub fn expected_stdout(&self) -> Option<&str> {
+        match self {
+            Self::Pass => None,
+            Self::Exact { text }
+            | Self::SplitWhitespace { text }
+            | Self::Lines { text }
+            | Self::CaseInsensitive { text }
+            | Self::UnorderedLines { text }
+            | Self::UnorderedWords { text }
+            | Self::Float { text, .. }
+            | Self::Tokens { text, .. } => Some(text),
+            Self::Regex { text } => Some(text.as_str()),
+        }
+    }
+}
+
+/// The expected output of `Regex`, with each line compiled as a pattern.
+#[derive(Debug, Clone)]
+pub struct RegexLines {
+    text: Arc<str>,
+    patterns: Arc<[Regex]>,
+}
+
+impl RegexLines {
+    pub fn new(text: Arc<str>) -> anyhow::Result<Self> {
+        let patterns = text
+            .lines()
+            .map(|pattern| {
+                Regex::new(&format!("^(?:{})$", pattern))
+                    .with_context(|| format!("Invalid regex: {:?}", pattern))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { text, patterns })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    fn is_match(&self, actual: &str) -> bool {
+        self.patterns
+            .iter()
+            .zip_longest(actual.lines())
+            .all(|zip| matches!(zip, EitherOrBoth::Both(pattern, line) if pattern.is_match(line)))
+    }
+}
+
+impl PartialEq for RegexLines {
+    fn eq(&self, other: &Self) -> bool {
+        self.text == other.text
+    }
+}
+
+impl Serialize for RegexLines {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.text)
+    }
+}
+
+impl<'de> Deserialize<'de> for RegexLines {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let text = String::deserialize(deserializer)?;
+        Self::new(text.into()).map_err(|e| D::Error::custom(format!("{:#}", e)))
+    }
+}
+
+/// An amount of memory.
+///
+/// Written as a number of bytes or a string such as `256 MiB` and `1GB`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteSize(u64);
+
+impl ByteSize {
+    const UNITS: &'static [(&'static str, u64)] = &[
+        ("GiB", 1 << 30),
+        ("MiB", 1 << 20),
+        ("KiB", 1 << 10),
+        ("GB", 1_000_000_000),
+        ("MB", 1_000_000),
+        ("KB", 1_000),
+        ("B", 1),
+    ];
+
+    pub fn from_bytes(bytes: u64) -> Self {
+        Self(bytes)
+    }
+
+    pub fn bytes(self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (unit, size) = [("MiB", 1 << 20), ("KiB", 1 << 10)]
+            .iter()
+            .copied()
+            .find(|&(_, size)| self.0 > 0 && self.0 % size == 0)
+            .unwrap_or(("B", 1));
+        write!(f, "{} {}", self.0 / size, unit)
+    }
+}
+
+impl FromStr for ByteSize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let s = s.trim();
+        let (number, unit) = s.split_at(
+            s.find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(s.len()),
+        );
+        let unit = unit.trim();
 
-                text.zip_longest(actual).all(|zip| {
-                    if let EitherOrBoth::Both(line1, line2) = zip {
-                        let (words1, words2) = (line1.split_whitespace(), line2.split_whitespace());
-                        words1.zip_longest(words2).all(|zip| match zip {
-                            EitherOrBoth::Both(s1, s2) => {
-                                match (s1.parse::<f64>(), s2.parse::<f64>()) {
-                                    (Ok(v1), Ok(v2)) => {
-                                        (v1 - v2).abs() <= absolute_error
-                                            || ((v1 - v2) / v2).abs() <= relative_error
-                                    }
-                                    _ => s1 == s2,
-                                }
-                            }
-                            EitherOrBoth::Left(_) | EitherOrBoth::Right(_) => false,
-                        })
-                    } else {
-                        false
-                    }
-                })
-            }
-        }
+        let (_, size) = Self::UNITS
+            .iter()
+            .find(|(u, _)| u.eq_ignore_ascii_case(unit) || unit.is_empty() && *u == "B")
+            .with_context(|| format!("unknown unit: {:?}", unit))?;
+
+        let number = number
+            .parse::<f64>()
+            .with_context(|| format!("invalid number: {:?}", number))?;
+
+        Ok(Self((number * *size as f64).round() as u64))
     }
+}
 
-    pub fn expected_stdout(&self) -> Option<&str> {
-        match self {
-            Self::Pass => None,
-            Self::Exact { text }
-            | Self::SplitWhitespace { text }
-            | Self::Lines { text }
-            | Self::Float { text, .. } => Some(text),
+impl Serialize for ByteSize {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Bytes(u64),
+            String(String),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Bytes(bytes) => Ok(Self(bytes)),
+            Repr::String(s) => s.parse().map_err(D::Error::custom),
         }
     }
 }
@@ -576,17 +1809,39 @@ mod serde_fn {
             Option::<String>::deserialize(deserializer).map(|s| s.map(Into::into))
         }
     }
+
+    pub(super) mod vec_arc_str {
+        use serde::{Deserialize, Deserializer, Serializer};
+        use std::sync::Arc;
+
+        pub(crate) fn serialize<S>(this: &[Arc<str>], serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serializer.collect_seq(this.iter().map(|s| &**s))
+        }
+
+        pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Vec<Arc<str>>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            Vec::<String>::deserialize(deserializer)
+                .map(|v| v.into_iter().map(Into::into).collect())
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        Additional, BatchTestSuite, DeterministicExpectedOutput, Match, PartialBatchTestCase,
-        PositiveFinite, TestSuite,
+        Additional, BatchTestSuite, ByteSize, CheckerCommand, CheckerProtocol, CheckerShell,
+        DeterministicExpectedOutput, ExpectedOutput, InteractiveTestCase, Interactor, Match,
+        Normalize, PartialBatchTestCase, PositiveFinite, Seeds, TestSuite, TimelimitMode,
+        ToleranceMode, Validator, ValidatorProtocol,
     };
     use difference::assert_diff;
     use pretty_assertions::assert_eq;
-    use std::time::Duration;
+    use std::{collections::HashSet, path::Path, time::Duration};
 
     #[test]
     fn atcoder_abc162_a() {
@@ -617,28 +1872,45 @@ extend: []
 "#,
             &TestSuite::Batch(BatchTestSuite {
                 timelimit: Some(Duration::from_secs(2)),
+                memory_limit: None,
+                timelimit_mode: TimelimitMode::WallTime,
+                presentation_error: false,
+                normalize: Normalize::default(),
+                validator: None,
                 r#match: Match::Lines,
                 cases: vec![
                     PartialBatchTestCase {
                         name: Some("Sample 1".to_owned()),
                         r#in: "117\n".into(),
                         out: Some("Yes\n".into()),
+                        alternatives: vec![],
                         timelimit: None,
+                        memory_limit: None,
                         r#match: None,
+                        presentation_error: None,
+                        normalize: None,
                     },
                     PartialBatchTestCase {
                         name: Some("Sample 2".to_owned()),
                         r#in: "123\n".into(),
                         out: Some("No\n".into()),
+                        alternatives: vec![],
                         timelimit: None,
+                        memory_limit: None,
                         r#match: None,
+                        presentation_error: None,
+                        normalize: None,
                     },
                     PartialBatchTestCase {
                         name: Some("Sample 3".to_owned()),
                         r#in: "777\n".into(),
                         out: Some("Yes\n".into()),
+                        alternatives: vec![],
                         timelimit: None,
+                        memory_limit: None,
                         r#match: None,
+                        presentation_error: None,
+                        normalize: None,
                     },
                 ],
                 extend: vec![],
@@ -661,6 +1933,11 @@ extend:
 "#,
             &TestSuite::Batch(BatchTestSuite {
                 timelimit: Some(Duration::from_secs(2)),
+                memory_limit: None,
+                timelimit_mode: TimelimitMode::WallTime,
+                presentation_error: false,
+                normalize: Normalize::default(),
+                validator: None,
                 r#match: Match::Lines,
                 cases: vec![],
                 extend: vec![Additional::Text {
@@ -668,7 +1945,10 @@ extend:
                     r#in: "/in/*.txt".into(),
                     out: "/out/*.txt".into(),
                     timelimit: None,
+                    memory_limit: None,
                     r#match: None,
+                    presentation_error: None,
+                    normalize: None,
                 }],
             }),
         );
@@ -701,24 +1981,39 @@ extend: []
 "#,
             &TestSuite::Batch(BatchTestSuite {
                 timelimit: Some(Duration::from_secs(2)),
+                memory_limit: None,
+                timelimit_mode: TimelimitMode::WallTime,
+                presentation_error: false,
+                normalize: Normalize::default(),
+                validator: None,
                 r#match: Match::Float {
                     relative_error: Some(PositiveFinite(0.01)),
                     absolute_error: Some(PositiveFinite(0.01)),
+                    tolerance_mode: ToleranceMode::Either,
+                    min_decimal_places: None,
                 },
                 cases: vec![
                     PartialBatchTestCase {
                         name: Some("Sample 1".to_owned()),
                         r#in: "1\n".into(),
                         out: Some("6.28318530717958623200\n".into()),
+                        alternatives: vec![],
                         timelimit: None,
+                        memory_limit: None,
                         r#match: None,
+                        presentation_error: None,
+                        normalize: None,
                     },
                     PartialBatchTestCase {
                         name: Some("Sample 2".to_owned()),
                         r#in: "73\n".into(),
                         out: Some("458.67252742410977361942\n".into()),
+                        alternatives: vec![],
                         timelimit: None,
+                        memory_limit: None,
                         r#match: None,
+                        presentation_error: None,
+                        normalize: None,
                     },
                 ],
                 extend: vec![],
@@ -755,28 +2050,583 @@ extend: []
 "#,
             &TestSuite::Batch(BatchTestSuite {
                 timelimit: Some(Duration::from_secs(2)),
+                timelimit_mode: TimelimitMode::WallTime,
+                presentation_error: false,
+                normalize: Normalize::default(),
+                memory_limit: None,
+                validator: None,
                 r#match: Match::Lines,
                 cases: vec![
                     PartialBatchTestCase {
                         name: Some("Sample 1".to_owned()),
                         r#in: "3\ncbaa\ndaacc\nacacac\n".into(),
                         out: Some("aac\n".into()),
+                        alternatives: vec![],
                         timelimit: None,
+                        memory_limit: None,
                         r#match: None,
+                        presentation_error: None,
+                        normalize: None,
                     },
                     PartialBatchTestCase {
                         name: Some("Sample 2".to_owned()),
                         r#in: "3\na\naa\nb\n".into(),
                         out: Some("\n".into()),
+                        alternatives: vec![],
+                        timelimit: None,
+                        memory_limit: None,
+                        r#match: None,
+                        presentation_error: None,
+                        normalize: None,
+                    },
+                ],
+                extend: vec![],
+            }),
+        );
+    }
+
+    #[test]
+    fn resource_limits() {
+        test_serialize_deserialize(
+            r#"---
+type: Batch
+timelimit: 2s
+memory_limit: 1024 MiB
+timelimit_mode: CpuTime
+match: Lines
+
+cases:
+  - name: Sample 1
+    in: |
+      1
+    out: |
+      1
+    timelimit: 1s
+    memory_limit: 256 MiB
+
+extend: []
+"#,
+            &TestSuite::Batch(BatchTestSuite {
+                timelimit: Some(Duration::from_secs(2)),
+                memory_limit: Some(ByteSize::from_bytes(1024 * 1024 * 1024)),
+                timelimit_mode: TimelimitMode::CpuTime,
+                presentation_error: false,
+                normalize: Normalize::default(),
+                validator: None,
+                r#match: Match::Lines,
+                cases: vec![PartialBatchTestCase {
+                    name: Some("Sample 1".to_owned()),
+                    r#in: "1\n".into(),
+                    out: Some("1\n".into()),
+                    alternatives: vec![],
+                    timelimit: Some(Duration::from_secs(1)),
+                    memory_limit: Some(ByteSize::from_bytes(256 * 1024 * 1024)),
+                    r#match: None,
+                    presentation_error: None,
+                    normalize: None,
+                }],
+                extend: vec![],
+            }),
+        );
+    }
+
+    #[test]
+    fn presentation_error() {
+        let suite = serde_yaml::from_str::<BatchTestSuite>(
+            r#"
+timelimit: 2s
+presentation_error: true
+match: Exact
+cases:
+  - in: "1\n"
+    out: "Yes\n"
+  - in: "2\n"
+    out: "No\n"
+    presentation_error: false
+"#,
+        )
+        .unwrap();
+        assert!(suite.presentation_error);
+
+        let cases = suite
+            .load_test_cases(Path::new(""), None::<HashSet<String>>, |_| Ok(vec![]))
+            .unwrap();
+        assert!(cases[0].presentation_error);
+        assert!(!cases[1].presentation_error);
+    }
+
+    #[test]
+    fn match_modes() {
+        let accepts = |r#match: Match, expected: &str, actual: &str| {
+            DeterministicExpectedOutput::new(expected.into(), r#match)
+                .unwrap()
+                .accepts(actual)
+        };
+
+        assert!(accepts(Match::CaseInsensitive, "Yes\n", "YES\n"));
+        assert!(!accepts(Match::CaseInsensitive, "Yes\n", "No\n"));
+        assert!(accepts(Match::UnorderedLines, "1 2\n3 4\n", "3 4\n1 2\n"));
+        assert!(!accepts(Match::UnorderedLines, "1 2\n3 4\n", "2 1\n3 4\n"));
+        assert!(accepts(Match::UnorderedWords, "1 2\n3 4\n", "4 3 2\n1\n"));
+        assert!(!accepts(Match::UnorderedWords, "1 1 2\n", "1 2 2\n"));
+        assert!(accepts(Match::Regex, "Yes|No\n\\d+\n", "No\n42\n"));
+        assert!(!accepts(Match::Regex, "Yes|No\n\\d+\n", "Maybe\n42\n"));
+        assert!(!accepts(Match::Regex, "\\d+\n", "42x\n"));
+        assert!(!accepts(Match::Regex, "\\d+\n", "42\n43\n"));
+        assert!(DeterministicExpectedOutput::new("(\n".into(), Match::Regex).is_err());
+    }
+
+    #[test]
+    fn alternatives() {
+        let suite = r#"---
+type: Batch
+timelimit: 2s
+match: Lines
+
+cases:
+  - name: Sample 1
+    in: |
+      4
+    out: |
+      1 3
+    alternatives:
+      - "3 1\n"
+      - "2 2\n"
+
+extend: []
+"#;
+
+        test_serialize_deserialize(
+            suite,
+            &TestSuite::Batch(BatchTestSuite {
+                timelimit: Some(Duration::from_secs(2)),
+                memory_limit: None,
+                timelimit_mode: TimelimitMode::WallTime,
+                presentation_error: false,
+                normalize: Normalize::default(),
+                validator: None,
+                r#match: Match::Lines,
+                cases: vec![PartialBatchTestCase {
+                    name: Some("Sample 1".to_owned()),
+                    r#in: "4\n".into(),
+                    out: Some("1 3\n".into()),
+                    alternatives: vec!["3 1\n".into(), "2 2\n".into()],
+                    timelimit: None,
+                    memory_limit: None,
+                    r#match: None,
+                    presentation_error: None,
+                    normalize: None,
+                }],
+                extend: vec![],
+            }),
+        );
+
+        let suite = match serde_yaml::from_str(suite).unwrap() {
+            TestSuite::Batch(suite) => suite,
+            _ => unreachable!(),
+        };
+        let cases = suite
+            .load_test_cases(Path::new(""), None::<HashSet<String>>, |_| unreachable!())
+            .unwrap();
+        let accepts = |actual| {
+            cases[0]
+                .output
+                .candidates()
+                .iter()
+                .any(|c| c.accepts(actual))
+        };
+        assert!(accepts("2 2\n"));
+        assert!(accepts("3 1\n"));
+        assert!(!accepts("4 0\n"));
+
+        let replaced = cases[0]
+            .output
+            .with_expected_stdout("4 0\n".into())
+            .unwrap();
+        assert_eq!(1, replaced.candidates().len());
+        assert!(replaced.candidates()[0].accepts("4 0\n"));
+    }
+
+    #[test]
+    fn tokens() {
+        let suite = serde_yaml::from_str::<TestSuite>(
+            r#"---
+type: Batch
+timelimit: 2s
+match:
+  Tokens:
+    rules:
+      - line: 1
+        match: Exact
+      - column: 2
+        match:
+          Float:
+            relative_error: 1e-6
+            absolute_error: 1e-6
+cases: []
+"#,
+        )
+        .unwrap();
+
+        let r#match = match suite {
+            TestSuite::Batch(BatchTestSuite { r#match, .. }) => r#match,
+            _ => unreachable!(),
+        };
+        let expected =
+            DeterministicExpectedOutput::new("3\n1 0.5\n2 0.25\n".into(), r#match).unwrap();
+
+        assert!(expected.accepts("3\n1 0.5000001\n2 0.25\n"));
+        assert!(!expected.accepts("3.0000001\n1 0.5\n2 0.25\n"));
+        assert!(!expected.accepts("3\n1.0000001 0.5\n2 0.25\n"));
+        assert!(!expected.accepts("3\n1 0.51\n2 0.25\n"));
+    }
+
+    #[test]
+    fn normalize() {
+        test_serialize_deserialize(
+            r#"---
+type: Batch
+timelimit: 2s
+normalize:
+  crlf: true
+  trailing_blank_lines: true
+match: Exact
+
+cases:
+  - name: Sample 1
+    in: |
+      1
+    out: |
+      1
+    normalize:
+      trailing_whitespace: true
+
+extend: []
+"#,
+            &TestSuite::Batch(BatchTestSuite {
+                timelimit: Some(Duration::from_secs(2)),
+                memory_limit: None,
+                timelimit_mode: TimelimitMode::WallTime,
+                presentation_error: false,
+                normalize: Normalize {
+                    crlf: true,
+                    trailing_blank_lines: true,
+                    ..Normalize::default()
+                },
+                validator: None,
+                r#match: Match::Exact,
+                cases: vec![PartialBatchTestCase {
+                    name: Some("Sample 1".to_owned()),
+                    r#in: "1\n".into(),
+                    out: Some("1\n".into()),
+                    alternatives: vec![],
+                    timelimit: None,
+                    memory_limit: None,
+                    r#match: None,
+                    presentation_error: None,
+                    normalize: Some(Normalize {
+                        trailing_whitespace: true,
+                        ..Normalize::default()
+                    }),
+                }],
+                extend: vec![],
+            }),
+        );
+
+        let normalize = Normalize {
+            crlf: true,
+            trailing_whitespace: true,
+            trailing_blank_lines: true,
+            nfc: true,
+        };
+        assert_eq!("1 2\n3\n", normalize.apply("1 2 \r\n3\r\n\r\n  \n"));
+        assert_eq!("1\n2", normalize.apply("1\n2"));
+        assert_eq!("", normalize.apply("\n\n"));
+        assert_eq!("\u{e9}\n", normalize.apply("e\u{301}\n"));
+        assert_eq!("1 \r\n", Normalize::default().apply("1 \r\n"));
+    }
+
+    #[test]
+    fn generated() {
+        test_serialize_deserialize(
+            r#"---
+type: Batch
+timelimit: 2s
+match: Lines
+
+cases: []
+
+extend:
+  - type: Generated
+    generator: python3 ./gen.py
+    reference:
+      - "./a.out"
+    seeds:
+      start: 1
+      end: 4
+  - type: Generated
+    generator: python3 ./gen.py --large
+    seeds:
+      - 10
+      - 20
+    name: large
+    cache: "./cache"
+    depends:
+      - "./params.txt"
+    generator_timelimit: 30s
+    timelimit: 5s
+"#,
+            &TestSuite::Batch(BatchTestSuite {
+                timelimit: Some(Duration::from_secs(2)),
+                memory_limit: None,
+                timelimit_mode: TimelimitMode::WallTime,
+                presentation_error: false,
+                normalize: Normalize::default(),
+                validator: None,
+                r#match: Match::Lines,
+                cases: vec![],
+                extend: vec![
+                    Additional::Generated {
+                        generator: "python3 ./gen.py".into(),
+                        reference: Some(CheckerCommand::Argv(vec!["./a.out".to_owned()])),
+                        shell: None,
+                        seeds: Seeds::Range { start: 1, end: 4 },
+                        name: None,
+                        cache: None,
+                        depends: vec![],
+                        generator_timelimit: None,
                         timelimit: None,
+                        memory_limit: None,
                         r#match: None,
+                        presentation_error: None,
+                        normalize: None,
+                    },
+                    Additional::Generated {
+                        generator: "python3 ./gen.py --large".into(),
+                        reference: None,
+                        shell: None,
+                        seeds: Seeds::List(vec![10, 20]),
+                        name: Some("large".to_owned()),
+                        cache: Some("./cache".into()),
+                        depends: vec!["./params.txt".into()],
+                        generator_timelimit: Some(Duration::from_secs(30)),
+                        timelimit: Some(Duration::from_secs(5)),
+                        memory_limit: None,
+                        r#match: None,
+                        presentation_error: None,
+                        normalize: None,
                     },
                 ],
+            }),
+        );
+
+        assert_eq!(
+            vec![1, 2, 3],
+            Seeds::Range { start: 1, end: 4 }.iter().collect::<Vec<_>>(),
+        );
+        assert_eq!(
+            vec![20, 10],
+            Seeds::List(vec![20, 10]).iter().collect::<Vec<_>>(),
+        );
+    }
+
+    #[test]
+    fn validator() {
+        test_serialize_deserialize(
+            r#"---
+type: Batch
+timelimit: 2s
+validator:
+  cmd:
+    - "./validator"
+  protocol: Testlib
+match: Lines
+
+cases:
+  - name: Sample 1
+    in: |
+      1
+    out: |
+      1
+
+extend: []
+"#,
+            &TestSuite::Batch(BatchTestSuite {
+                timelimit: Some(Duration::from_secs(2)),
+                memory_limit: None,
+                timelimit_mode: TimelimitMode::WallTime,
+                presentation_error: false,
+                normalize: Normalize::default(),
+                validator: Some(Validator {
+                    cmd: CheckerCommand::Argv(vec!["./validator".to_owned()]),
+                    shell: None,
+                    protocol: ValidatorProtocol::Testlib,
+                    timelimit: None,
+                }),
+                r#match: Match::Lines,
+                cases: vec![PartialBatchTestCase {
+                    name: Some("Sample 1".to_owned()),
+                    r#in: "1\n".into(),
+                    out: Some("1\n".into()),
+                    alternatives: vec![],
+                    timelimit: None,
+                    memory_limit: None,
+                    r#match: None,
+                    presentation_error: None,
+                    normalize: None,
+                }],
+                extend: vec![],
+            }),
+        );
+    }
+
+    #[test]
+    fn custom() {
+        let suite = serde_yaml::from_str::<BatchTestSuite>(
+            r#"
+timelimit: 2s
+match:
+  Custom:
+    name: graph
+cases:
+  - in: "1\n"
+    out: "1\n"
+"#,
+        )
+        .unwrap();
+        let cases = suite
+            .load_test_cases(Path::new(""), None::<HashSet<String>>, |_| Ok(vec![]))
+            .unwrap();
+        assert_eq!(
+            ExpectedOutput::Custom {
+                text: Some("1\n".into()),
+                name: "graph".to_owned(),
+            },
+            cases[0].output,
+        );
+        assert_eq!(Some("1\n"), cases[0].output.example());
+        assert!(cases[0].output.candidates().is_empty());
+    }
+
+    #[test]
+    fn byte_size() {
+        assert_eq!(
+            256 * 1024 * 1024,
+            "256 MiB".parse::<ByteSize>().unwrap().bytes(),
+        );
+        assert_eq!(1_000_000_000, "1GB".parse::<ByteSize>().unwrap().bytes());
+        assert_eq!(1536, "1.5 KiB".parse::<ByteSize>().unwrap().bytes());
+        assert_eq!(1000, "1000".parse::<ByteSize>().unwrap().bytes());
+        assert!("1 MiBs".parse::<ByteSize>().is_err());
+        assert_eq!("1024 MiB", ByteSize::from_bytes(1 << 30).to_string());
+        assert_eq!("1000000 B", ByteSize::from_bytes(1_000_000).to_string());
+    }
+
+    #[test]
+    fn testlib_checker() {
+        test_serialize_deserialize(
+            r#"---
+type: Batch
+timelimit: 2s
+match:
+  Checker:
+    cmd: "./checker"
+    shell: Bash
+    protocol: Testlib
+    timelimit: 10s
+
+cases:
+  - name: Sample 1
+    in: |
+      4
+    out: |
+      2 2
+
+extend: []
+"#,
+            &TestSuite::Batch(BatchTestSuite {
+                timelimit: Some(Duration::from_secs(2)),
+                memory_limit: None,
+                timelimit_mode: TimelimitMode::WallTime,
+                presentation_error: false,
+                normalize: Normalize::default(),
+                validator: None,
+                r#match: Match::Checker {
+                    cmd: "./checker".into(),
+                    shell: Some(CheckerShell::Bash),
+                    protocol: CheckerProtocol::Testlib,
+                    timelimit: Some(Duration::from_secs(10)),
+                },
+                cases: vec![PartialBatchTestCase {
+                    name: Some("Sample 1".to_owned()),
+                    r#in: "4\n".into(),
+                    out: Some("2 2\n".into()),
+                    alternatives: vec![],
+                    timelimit: None,
+                    memory_limit: None,
+                    r#match: None,
+                    presentation_error: None,
+                    normalize: None,
+                }],
                 extend: vec![],
             }),
         );
     }
 
+    #[test]
+    fn interactive() {
+        let suite = serde_yaml::from_str::<TestSuite>(
+            r#"---
+type: Interactive
+timelimit: 2s
+memory_limit: 1024 MiB
+timelimit_mode: CpuTime
+interactor:
+  cmd: [./interactor, --strict]
+  protocol: Testlib
+cases:
+  - name: "1"
+    in: |
+      42
+  - name: "2"
+    in: |
+      7
+    timelimit: 1s
+    memory_limit: 256 MiB
+"#,
+        )
+        .unwrap();
+
+        let suite = match suite {
+            TestSuite::Interactive(suite) => suite,
+            _ => panic!(),
+        };
+
+        let interactor = Interactor {
+            cmd: CheckerCommand::Argv(vec!["./interactor".to_owned(), "--strict".to_owned()]),
+            shell: None,
+            protocol: CheckerProtocol::Testlib,
+        };
+
+        assert_eq!(
+            vec![InteractiveTestCase {
+                name: Some("2".to_owned()),
+                timelimit: Some(Duration::from_secs(1)),
+                memory_limit: Some(ByteSize::from_bytes(256 * 1024 * 1024)),
+                timelimit_mode: TimelimitMode::CpuTime,
+                input: "7\n".into(),
+                interactor,
+            }],
+            suite.load_test_cases(Some(hashset_of(&["2"]))).unwrap(),
+        );
+
+        assert!(serde_yaml::from_str::<TestSuite>("type: Interactive\ntimelimit: 2s\n").is_ok());
+    }
+
+    fn hashset_of<'a>(names: &[&'a str]) -> HashSet<&'a str> {
+        names.iter().copied().collect()
+    }
+
     fn test_serialize_deserialize(yaml: &str, expected: &TestSuite) {
         let actual = serde_yaml::from_str::<TestSuite>(yaml).unwrap();
         assert_eq!(*expected, actual);
@@ -841,6 +2691,8 @@ extend: []
             text: "10000.0\n".into(),
             relative_error: Some(PositiveFinite(0.01)),
             absolute_error: None,
+            tolerance_mode: ToleranceMode::Either,
+            min_decimal_places: None,
         }
         .accepts("10001.0\n"));
 
@@ -848,7 +2700,59 @@ extend: []
             text: "10000.0\n".into(),
             relative_error: Some(PositiveFinite(0.01)),
             absolute_error: None,
+            tolerance_mode: ToleranceMode::Either,
+            min_decimal_places: None,
         }
         .accepts("0\n"));
     }
+
+    #[test]
+    fn float_tolerance() {
+        let float = |relative_error: Option<f64>,
+                     absolute_error: Option<f64>,
+                     tolerance_mode,
+                     min_decimal_places| {
+            DeterministicExpectedOutput::Float {
+                text: "".into(),
+                relative_error: relative_error.map(PositiveFinite),
+                absolute_error: absolute_error.map(PositiveFinite),
+                tolerance_mode,
+                min_decimal_places,
+            }
+        };
+
+        // The relative error is computed against the expected value.
+        let expected = float(Some(0.5), None, ToleranceMode::Either, None);
+        assert!(expected.accepts_word("2", "1"));
+        assert!(!expected.accepts_word("1", "2"));
+
+        let expected = float(Some(1e-6), Some(1e-6), ToleranceMode::Either, None);
+        assert!(expected.accepts_word("1000000", "1000000.5"));
+        assert!(expected.accepts_word("0.0000001", "0.0000005"));
+        assert!(expected.accepts_word("nan", "NaN"));
+        assert!(!expected.accepts_word("nan", "0"));
+        assert!(expected.accepts_word("inf", "inf"));
+        assert!(!expected.accepts_word("inf", "-inf"));
+        assert!(!expected.accepts_word("1", "inf"));
+
+        let expected = float(Some(1e-6), Some(1e-6), ToleranceMode::Both, None);
+        assert!(!expected.accepts_word("1000000", "1000000.5"));
+        assert!(!expected.accepts_word("0.0000001", "0.0000005"));
+        assert!(expected.accepts_word("1", "1.0000001"));
+        assert!(expected.accepts_word("0", "0"));
+        assert!(expected.accepts_word("0", "1e-9"));
+        assert!(!expected.accepts_word("0", "1e-5"));
+
+        let expected = float(Some(1e-6), None, ToleranceMode::Both, None);
+        assert!(expected.accepts_word("0", "0"));
+        assert!(!expected.accepts_word("0", "1e-9"));
+
+        let expected = float(None, Some(1e-6), ToleranceMode::Both, None);
+        assert!(expected.accepts_word("1000000", "1000000.0000005"));
+
+        let expected = float(None, Some(1e-6), ToleranceMode::Either, Some(3));
+        assert!(expected.accepts_word("0.500", "0.500"));
+        assert!(!expected.accepts_word("0.500", "0.5"));
+        assert!(expected.accepts_word("3", "3"));
+    }
 }